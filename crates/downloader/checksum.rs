@@ -0,0 +1,343 @@
+#![deny(warnings)]
+
+use core::result::Result;
+use std::{fmt, fs::File, io::Read, path::Path, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use eyre::Report;
+use sha2::{Digest, Sha256, Sha512};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    // the bytes of digest
+    fn len(&self) -> usize {
+        match self {
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha512 => 64,
+            Algorithm::Blake3 => 32,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Algorithm> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            "blake3" => Some(Algorithm::Blake3),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm, // The digest algorithm
+    pub digest: Vec<u8>,      // The expected digest
+}
+
+#[derive(Error, Debug)]
+#[error("The file {algorithm} is '{actual}' but expect '{expected}'")]
+pub struct ChecksumMismatchError {
+    pub algorithm: &'static str,
+    pub actual: String,
+    pub expected: String,
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+// parse the checksum. eg.
+// 1. hex digest, the algorithm is inferred from the length (SHA256 or SHA512)
+// 2. prefixed hex digest. eg. sha256:<hex>, sha512:<hex>, blake3:<hex>
+// 3. Subresource Integrity. eg. sha256-<base64>
+impl FromStr for Checksum {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let invalid = || eyre::format_err!("invalid checksum '{}'", s);
+
+        let (algorithm, digest) = if let Some((name, hex)) = s.split_once(':') {
+            (
+                Algorithm::parse(name).ok_or_else(invalid)?,
+                from_hex(hex).ok_or_else(invalid)?,
+            )
+        } else if let Some((name, b64)) = s.split_once('-') {
+            (
+                Algorithm::parse(name).ok_or_else(invalid)?,
+                STANDARD.decode(b64).map_err(|_| invalid())?,
+            )
+        } else {
+            let digest = from_hex(s).ok_or_else(invalid)?;
+
+            let algorithm = match digest.len() {
+                64 => Algorithm::Sha512,
+                _ => Algorithm::Sha256,
+            };
+
+            (algorithm, digest)
+        };
+
+        if digest.len() != algorithm.len() {
+            return Err(invalid());
+        }
+
+        Ok(Checksum { algorithm, digest })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm.as_str(), self.hex())
+    }
+}
+
+impl Checksum {
+    pub fn hex(&self) -> String {
+        to_hex(&self.digest)
+    }
+
+    // find the checksum of file in the published checksum file. eg. SHA256SUMS
+    // 1. GNU coreutils: <hex>  <filename> or <hex> *<filename>
+    // 2. BSD: SHA256 (<filename>) = <hex>
+    pub fn from_checksum_file(content: &str, filename: &str) -> Result<Option<Checksum>, Report> {
+        for line in content.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // BSD format
+            if let Some((left, hex)) = line.rsplit_once(") = ") {
+                if let Some((name, file)) = left.split_once(" (") {
+                    if file == filename {
+                        return Ok(Some(format!("{}:{}", name, hex.trim()).parse()?));
+                    }
+                }
+
+                continue;
+            }
+
+            // GNU format, the '*' means binary mode
+            if let Some((hex, file)) = line.split_once(char::is_whitespace) {
+                let file = file.trim_start();
+                let file = file.strip_prefix('*').unwrap_or(file);
+
+                // the file may be listed with the folder. eg. ./dist/file.tar.gz
+                if file == filename || file.ends_with(&format!("/{}", filename)) {
+                    return Ok(Some(hex.parse()?));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // compare with the digest that computed
+    pub(crate) fn verify(&self, digest: &[u8]) -> Result<(), ChecksumMismatchError> {
+        if digest == self.digest.as_slice() {
+            Ok(())
+        } else {
+            Err(ChecksumMismatchError {
+                algorithm: self.algorithm.as_str(),
+                actual: to_hex(digest),
+                expected: self.hex(),
+            })
+        }
+    }
+
+    // verify the file that already on the disk. eg. the cached file
+    pub fn verify_file(&self, filepath: &Path) -> Result<(), Report> {
+        let mut file = File::open(filepath)?;
+        let mut hasher = Hasher::new(self.algorithm);
+        let mut buf = [0; 64 * 1024];
+
+        loop {
+            let n = file.read(&mut buf)?;
+
+            if n == 0 {
+                break;
+            }
+
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(self.verify(&hasher.finalize())?)
+    }
+}
+
+// compute the digest while the file is streaming
+pub(crate) enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: Algorithm) -> Hasher {
+        match algorithm {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::checksum::{Algorithm, Checksum};
+
+    // the digests of 'hello'
+    const SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    const SHA512: &str = "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca72323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043";
+    const BLAKE3: &str = "ea8f163db38682925e4491c5e58d4bb3506ef8c14eb78a86e908c5624a67200f";
+
+    #[test]
+    fn test_parse_checksum() {
+        let c: Checksum = SHA256.parse().unwrap();
+        assert_eq!(c.algorithm, Algorithm::Sha256);
+        assert_eq!(c.hex(), SHA256);
+
+        let c: Checksum = SHA256.to_uppercase().parse().unwrap();
+        assert_eq!(c.to_string(), format!("sha256:{}", SHA256));
+
+        let c: Checksum = SHA512.parse().unwrap();
+        assert_eq!(c.algorithm, Algorithm::Sha512);
+
+        let c: Checksum = format!("sha512:{}", SHA512).parse().unwrap();
+        assert_eq!(c.hex(), SHA512);
+
+        let c: Checksum = format!("BLAKE3:{}", BLAKE3).parse().unwrap();
+        assert_eq!(c.algorithm, Algorithm::Blake3);
+
+        // Subresource Integrity
+        let c: Checksum = "sha256-LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="
+            .parse()
+            .unwrap();
+        assert_eq!(c.algorithm, Algorithm::Sha256);
+        assert_eq!(c.hex(), SHA256);
+
+        assert!("".parse::<Checksum>().is_err());
+        assert!("md5:5d41402abc4b2a76b9719d911017c592"
+            .parse::<Checksum>()
+            .is_err());
+        assert!(
+            "sha512:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                .parse::<Checksum>()
+                .is_err()
+        );
+        assert!("sha256-invalid".parse::<Checksum>().is_err());
+        assert!("not a hex".parse::<Checksum>().is_err());
+    }
+
+    #[test]
+    fn test_from_checksum_file() {
+        let gnu = format!(
+            "{}  dvm_linux_amd64.tar.gz\n{} *dvm_darwin_amd64.tar.gz\n{}  ./dist/dvm_windows_amd64.zip\n",
+            SHA256, SHA512, SHA256
+        );
+
+        let c = Checksum::from_checksum_file(&gnu, "dvm_linux_amd64.tar.gz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(c.to_string(), format!("sha256:{}", SHA256));
+
+        let c = Checksum::from_checksum_file(&gnu, "dvm_darwin_amd64.tar.gz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(c.algorithm, Algorithm::Sha512);
+
+        let c = Checksum::from_checksum_file(&gnu, "dvm_windows_amd64.zip")
+            .unwrap()
+            .unwrap();
+        assert_eq!(c.hex(), SHA256);
+
+        assert!(Checksum::from_checksum_file(&gnu, "amd64.tar.gz")
+            .unwrap()
+            .is_none());
+
+        let bsd = format!(
+            "SHA256 (dvm_linux_amd64.tar.gz) = {}\nBLAKE3 (dvm_darwin_amd64.tar.gz) = {}\n",
+            SHA256, BLAKE3
+        );
+
+        let c = Checksum::from_checksum_file(&bsd, "dvm_darwin_amd64.tar.gz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(c.to_string(), format!("blake3:{}", BLAKE3));
+
+        assert!(Checksum::from_checksum_file(&bsd, "dvm.tar.gz")
+            .unwrap()
+            .is_none());
+
+        assert!(Checksum::from_checksum_file("invalid  dvm.tar.gz", "dvm.tar.gz").is_err());
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let file = env::temp_dir().join("cask_test_verify_file");
+
+        fs::write(&file, "hello").unwrap();
+
+        for checksum in [
+            SHA256.to_string(),
+            SHA512.to_string(),
+            format!("blake3:{}", BLAKE3),
+        ] {
+            let c: Checksum = checksum.parse().unwrap();
+            c.verify_file(&file).unwrap();
+        }
+
+        let c: Checksum = SHA256.replace('2', "3").parse().unwrap();
+
+        assert!(c.verify_file(&file).is_err());
+
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,910 @@
+#![deny(warnings)]
+
+use core::result::Result;
+use std::{
+    cmp::min,
+    fs,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::Report;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    Certificate, Client, ClientBuilder, NoProxy, Proxy, RequestBuilder, Response, StatusCode,
+};
+
+use crate::{checksum::Hasher, retry::HttpStatusError, DownloadOption};
+
+// create the client builder with the TLS backend. eg. rustls or openssl
+pub(crate) type Backend = fn() -> ClientBuilder;
+
+fn build_client(backend: Backend, options: &DownloadOption) -> Result<Client, Report> {
+    let mut builder = backend();
+
+    // the proxy of environment variables is not used if any proxy provided
+    let no_proxy = options.no_proxy.as_deref().and_then(NoProxy::from_string);
+
+    if let Some(proxy) = &options.http_proxy {
+        builder = builder.proxy(
+            Proxy::http(proxy)
+                .map_err(|e| eyre::format_err!("invalid http proxy '{}': {}", proxy, e))?
+                .no_proxy(no_proxy.clone()),
+        );
+    }
+
+    if let Some(proxy) = &options.https_proxy {
+        builder = builder.proxy(
+            Proxy::https(proxy)
+                .map_err(|e| eyre::format_err!("invalid https proxy '{}': {}", proxy, e))?
+                .no_proxy(no_proxy),
+        );
+    }
+
+    if let Some(ca_file) = &options.ca_file {
+        let pem = fs::read(ca_file).map_err(|e| {
+            eyre::format_err!("can not read CA file '{}': {}", ca_file.display(), e)
+        })?;
+
+        for cert in Certificate::from_pem_bundle(&pem)
+            .map_err(|e| eyre::format_err!("invalid CA file '{}': {}", ca_file.display(), e))?
+        {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+fn request(
+    backend: Backend,
+    url: &str,
+    options: &DownloadOption,
+) -> Result<RequestBuilder, Report> {
+    let client = &build_client(backend, options)?;
+
+    let mut req = client.get(url);
+
+    if let Some(authorization) = &options.authorization {
+        let mut value = reqwest::header::HeaderValue::from_str(authorization)
+            .map_err(|_| eyre::format_err!("invalid authorization for {}", &url))?;
+
+        value.set_sensitive(true);
+
+        req = req.header(AUTHORIZATION, value);
+    }
+
+    Ok(req)
+}
+
+// make sure the size does not exceed the max size
+fn check_size(url: &str, size: u64, options: &DownloadOption) -> Result<(), Report> {
+    match options.max_size {
+        Some(max_size) if size > max_size => Err(eyre::format_err!(
+            "Download {} fail, the size exceeds the max size {} bytes",
+            &url,
+            max_size
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) async fn fetch(
+    backend: Backend,
+    url: &str,
+    options: &DownloadOption,
+) -> Result<Vec<u8>, Report> {
+    let res = request(backend, url, options)?.send().await?;
+
+    if res.status() != 200 {
+        return Err(HttpStatusError::new(url, &res).into());
+    }
+
+    let mut body: Vec<u8> = vec![];
+    let mut stream = res.bytes_stream();
+
+    while let Some(item) = stream.next().await {
+        body.extend_from_slice(&item?);
+
+        check_size(url, body.len() as u64, options)?;
+    }
+
+    Ok(body)
+}
+
+// the partial file and the file to save the validator of partial file
+fn get_part_paths(filepath: &Path) -> (PathBuf, PathBuf) {
+    let mut part = filepath.as_os_str().to_owned();
+    part.push(".part");
+
+    let mut meta = part.clone();
+    meta.push(".meta");
+
+    (PathBuf::from(part), PathBuf::from(meta))
+}
+
+// remove the partial file and its validator
+fn remove_part_files(part_path: &Path, meta_path: &Path) -> Result<(), Report> {
+    if part_path.exists() {
+        fs::remove_file(part_path)?;
+    }
+
+    if meta_path.exists() {
+        fs::remove_file(meta_path)?;
+    }
+
+    Ok(())
+}
+
+// get the validator for If-Range header, the weak ETag can not be used
+fn get_validator(res: &Response) -> Option<String> {
+    let headers = res.headers();
+
+    if let Some(etag) = headers.get(ETAG).and_then(|v| v.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            return Some(etag.to_string());
+        }
+    }
+
+    headers
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+// get the start position of Content-Range. eg. bytes 100-199/200
+fn get_range_start(res: &Response) -> Option<u64> {
+    res.headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+pub(crate) async fn download(
+    backend: Backend,
+    url: &str,
+    filepath: &Path,
+    options: &DownloadOption,
+) -> Result<(), Report> {
+    let (part_path, meta_path) = get_part_paths(filepath);
+
+    // resume from the partial file if it can be validated
+    let resume = match (fs::metadata(&part_path), fs::read_to_string(&meta_path)) {
+        (Ok(meta), Ok(validator)) if meta.len() > 0 && !validator.is_empty() => {
+            Some((meta.len(), validator))
+        }
+        _ => None,
+    };
+
+    let mut req = request(backend, url, options)?;
+
+    if let Some((size, validator)) = &resume {
+        req = req
+            .header(RANGE, format!("bytes={}-", size))
+            .header(IF_RANGE, validator.as_str());
+    }
+
+    let mut res = req.send().await?;
+
+    if res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file does not match the remote, download from the beginning
+        res = request(backend, url, options)?.send().await?;
+    }
+
+    let mut downloaded: u64 = match (res.status(), &resume) {
+        (StatusCode::PARTIAL_CONTENT, Some((size, _))) => {
+            if get_range_start(&res) != Some(*size) {
+                return Err(eyre::format_err!(
+                    "Download {} fail with invalid Content-Range",
+                    &url
+                ));
+            }
+
+            *size
+        }
+        // the server does not support range or the file has been changed
+        (StatusCode::OK, _) => 0,
+        _ => return Err(HttpStatusError::new(url, &res).into()),
+    };
+
+    if downloaded == 0 {
+        // the partial file can be resumed only if it has the validator
+        match get_validator(&res) {
+            Some(validator) => fs::write(&meta_path, validator)?,
+            None => {
+                if meta_path.exists() {
+                    fs::remove_file(&meta_path)?;
+                }
+            }
+        }
+    }
+
+    // the content length is unknown if the server uses chunked transfer encoding
+    let total_size = res.content_length().map(|len| downloaded + len);
+
+    if let Some(size) = total_size {
+        check_size(url, size, options)?;
+    }
+
+    let pb = match total_size {
+        Some(size) => {
+            let progress_template = "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+            let pb = ProgressBar::new(size);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .progress_chars("#>-")
+                    .template(progress_template)?,
+            );
+            pb
+        }
+        None => {
+            let progress_template =
+                "{msg}\n{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})";
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(ProgressStyle::default_spinner().template(progress_template)?);
+            pb
+        }
+    };
+    pb.set_message(format!("Downloading {}", url));
+    pb.set_position(downloaded);
+
+    let mut hasher = options.checksum.as_ref().map(|c| Hasher::new(c.algorithm));
+
+    let mut dest = if downloaded > 0 {
+        // feed the resumed part into the digest
+        if let Some(hasher) = hasher.as_mut() {
+            let mut part = File::open(&part_path)?;
+            let mut buf = [0; 64 * 1024];
+
+            loop {
+                let n = part.read(&mut buf)?;
+
+                if n == 0 {
+                    break;
+                }
+
+                hasher.update(&buf[..n]);
+            }
+        }
+
+        OpenOptions::new().append(true).open(&part_path)?
+    } else {
+        File::create(&part_path)?
+    };
+
+    let mut stream = res.bytes_stream();
+
+    while let Some(item) = stream.next().await {
+        let chunk = item.map_err(|e| Report::new(e).wrap_err("Error while downloading file"))?;
+
+        dest.write_all(&chunk)
+            .map_err(|_| eyre::format_err!("Error while write file"))?;
+
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+
+        downloaded += chunk.len() as u64;
+
+        if let Err(e) = check_size(url, downloaded, options) {
+            // the runaway response should not be resumed
+            drop(dest);
+            remove_part_files(&part_path, &meta_path)?;
+
+            return Err(e);
+        }
+
+        pb.set_position(match total_size {
+            Some(size) => min(downloaded, size),
+            None => downloaded,
+        });
+    }
+
+    pb.finish();
+
+    drop(dest);
+
+    if let (Some(checksum), Some(hasher)) = (&options.checksum, hasher) {
+        if let Err(e) = checksum.verify(&hasher.finalize()) {
+            // the broken file should not be resumed
+            remove_part_files(&part_path, &meta_path)?;
+
+            return Err(e.into());
+        }
+    }
+
+    // the download is complete
+    if filepath.exists() {
+        fs::remove_file(filepath)?;
+    }
+
+    fs::rename(&part_path, filepath)?;
+
+    if meta_path.exists() {
+        fs::remove_file(&meta_path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{
+            crypto::ring,
+            pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+            ServerConfig,
+        },
+        TlsAcceptor,
+    };
+
+    use crate::{download, download_from_mirrors, download_with, fetch, http, DownloadOption};
+
+    // the TLS backends to test
+    fn backends() -> Vec<(&'static str, http::Backend)> {
+        vec![
+            #[cfg(feature = "rustls")]
+            ("rustls", crate::rustls::client_builder as http::Backend),
+            #[cfg(feature = "openssl")]
+            ("openssl", crate::openssl::client_builder as http::Backend),
+        ]
+    }
+
+    // read the request head and write the response of handler
+    async fn respond<S, F>(socket: &mut S, handler: &F)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: Fn(&str) -> Vec<u8>,
+    {
+        let mut head = vec![];
+        let mut buf = [0; 1024];
+
+        while !head.ends_with(b"\r\n\r\n") {
+            let n = socket.read(&mut buf).await.unwrap_or(0);
+
+            if n == 0 {
+                break;
+            }
+
+            head.extend_from_slice(&buf[..n]);
+        }
+
+        let res = handler(&String::from_utf8_lossy(&head));
+
+        socket.write_all(&res).await.ok();
+        socket.shutdown().await.ok();
+    }
+
+    // start a http server in local, the handler receive the request head and return the response
+    async fn serve<F>(handler: F) -> String
+    where
+        F: Fn(&str) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                respond(&mut socket, &handler).await;
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    // start a https server in local with the certificate signed by a new CA.
+    // returns the url and the PEM of CA
+    async fn serve_tls<F>(handler: F) -> (String, String)
+    where
+        F: Fn(&str) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca_cert = {
+            let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+            params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, "Cask Test CA");
+            params.self_signed(&ca_key).unwrap()
+        };
+
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&key, &ca_cert, &ca_key)
+            .unwrap();
+
+        let config = ServerConfig::builder_with_provider(ring::default_provider().into())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            )
+            .unwrap();
+
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                // the client may reject the certificate
+                if let Ok(mut stream) = acceptor.accept(socket).await {
+                    respond(&mut stream, &handler).await;
+                }
+            }
+        });
+
+        (format!("https://localhost:{}", addr.port()), ca_cert.pem())
+    }
+
+    fn response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+        let mut res = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status);
+
+        for h in headers {
+            res += &format!("{}\r\n", h);
+        }
+
+        let mut res = format!("{}\r\n", res).into_bytes();
+
+        res.extend_from_slice(body);
+
+        res
+    }
+
+    #[tokio::test]
+
+    async fn test_download() {
+        let url =
+            "https://github.com/axetroy/prune.v/releases/download/v0.2.14/prune_darwin_amd64.tar.gz";
+
+        let cwd = env::current_dir().unwrap();
+
+        let dest = cwd.join("cask_darwin_amd64.tar.gz");
+
+        download(url, &dest).await.unwrap();
+
+        assert!(dest.exists());
+
+        let meta = fs::metadata(&dest).unwrap();
+
+        assert!(meta.is_file());
+        assert_eq!(meta.len(), 62_310);
+
+        fs::remove_file(&dest).unwrap();
+    }
+
+    #[tokio::test]
+
+    async fn test_download_invalid_url() {
+        let url = "https://github.com/axetroy/prune.v/releases/download/v0.2.14/not_exist.tar.gz";
+
+        let cwd = env::current_dir().unwrap();
+
+        let dest = cwd.join("cask_darwin_amd64.tar.gz");
+
+        let r = download(url, &dest).await;
+
+        assert!(r.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_download_with_authorization() {
+        let server = serve(|head| {
+            if head.contains("authorization: Bearer secret_token\r\n") {
+                response("200 OK", &["Content-Length: 5"], b"hello")
+            } else {
+                response("401 Unauthorized", &["Content-Length: 0"], b"")
+            }
+        })
+        .await;
+
+        let dest = env::temp_dir().join("cask_test_download_with_authorization");
+
+        let r = download(&format!("{}/file", server), &dest).await;
+
+        assert!(r.is_err());
+
+        download_with(
+            &format!("{}/file", server),
+            &dest,
+            &DownloadOption {
+                authorization: Some("Bearer secret_token".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"hello");
+
+        fs::remove_file(&dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let server = serve(|head| {
+            let etag = "ETag: \"v1\"";
+
+            if !head.contains("range: bytes=") {
+                // interrupt after sending the first part
+                response("200 OK", &["Content-Length: 11", etag], b"hello")
+            } else if head.contains("range: bytes=5-\r\n") && head.contains("if-range: \"v1\"\r\n")
+            {
+                response(
+                    "206 Partial Content",
+                    &["Content-Length: 6", "Content-Range: bytes 5-10/11", etag],
+                    b" world",
+                )
+            } else {
+                // the validator does not match
+                response("200 OK", &["Content-Length: 11", etag], b"HELLO WORLD")
+            }
+        })
+        .await;
+
+        let url = format!("{}/file", server);
+        let dest = env::temp_dir().join("cask_test_resume_download");
+        let part = env::temp_dir().join("cask_test_resume_download.part");
+        let meta = env::temp_dir().join("cask_test_resume_download.part.meta");
+
+        fs::remove_file(&dest).ok();
+
+        assert!(download(&url, &dest).await.is_err());
+        assert!(!dest.exists());
+        assert_eq!(fs::read(&part).unwrap(), b"hello");
+        assert_eq!(fs::read_to_string(&meta).unwrap(), "\"v1\"");
+
+        download(&url, &dest).await.unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"hello world");
+        assert!(!part.exists());
+        assert!(!meta.exists());
+
+        // the remote file has been changed
+        fs::write(&part, b"hello").unwrap();
+        fs::write(&meta, "\"v0\"").unwrap();
+
+        download(&url, &dest).await.unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"HELLO WORLD");
+
+        fs::remove_file(&dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_with_checksum() {
+        let server = serve(|head| {
+            let etag = "ETag: \"v1\"";
+
+            if !head.contains("range: bytes=") {
+                // interrupt after sending the first part
+                response("200 OK", &["Content-Length: 11", etag], b"hello")
+            } else {
+                response(
+                    "206 Partial Content",
+                    &["Content-Length: 6", "Content-Range: bytes 5-10/11", etag],
+                    b" world",
+                )
+            }
+        })
+        .await;
+
+        let url = format!("{}/file", server);
+        let dest = env::temp_dir().join("cask_test_download_with_checksum");
+        let part = env::temp_dir().join("cask_test_download_with_checksum.part");
+
+        fs::remove_file(&dest).ok();
+
+        let options = DownloadOption {
+            checksum: Some(
+                "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        };
+
+        // the digest includes the resumed part
+        assert!(download_with(&url, &dest, &options).await.is_err());
+        download_with(&url, &dest, &options).await.unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"hello world");
+
+        fs::remove_file(&dest).unwrap();
+
+        let options = DownloadOption {
+            checksum: Some(
+                "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        };
+
+        assert!(download_with(&url, &dest, &options).await.is_err());
+
+        let err = download_with(&url, &dest, &options).await.unwrap_err();
+
+        assert!(err.downcast_ref::<crate::ChecksumMismatchError>().is_some());
+        assert!(!dest.exists());
+        assert!(!part.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_with_retry() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+
+        let server = serve(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => response("502 Bad Gateway", &["Content-Length: 0"], b""),
+            1 => response(
+                "503 Service Unavailable",
+                &["Content-Length: 0", "Retry-After: 0"],
+                b"",
+            ),
+            _ => response("200 OK", &["Content-Length: 5"], b"hello"),
+        })
+        .await;
+
+        let dest = env::temp_dir().join("cask_test_download_with_retry");
+
+        let options = DownloadOption {
+            retries: 1,
+            retry_delay: Duration::from_millis(10),
+            max_retry_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+
+        // the retries run out
+        assert!(download_with(&format!("{}/file", server), &dest, &options)
+            .await
+            .is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        count.store(0, Ordering::SeqCst);
+
+        download_with(
+            &format!("{}/file", server),
+            &dest,
+            &DownloadOption {
+                retries: 3,
+                ..options
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(fs::read(&dest).unwrap(), b"hello");
+
+        fs::remove_file(&dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_from_mirrors() {
+        let primary = serve(|_| response("404 Not Found", &["Content-Length: 0"], b"")).await;
+        let mirror = serve(|_| response("200 OK", &["Content-Length: 5"], b"hello")).await;
+
+        let dest = env::temp_dir().join("cask_test_download_from_mirrors");
+
+        download_from_mirrors(
+            &[
+                (format!("{}/file", primary), DownloadOption::default()),
+                (format!("{}/file", mirror), DownloadOption::default()),
+            ],
+            &dest,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"hello");
+
+        fs::remove_file(&dest).unwrap();
+
+        // all of the mirrors fail
+        assert!(download_from_mirrors(
+            &[(format!("{}/file", primary), DownloadOption::default())],
+            &dest,
+        )
+        .await
+        .is_err());
+        assert!(download_from_mirrors(&[], &dest).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_with_proxy() {
+        // the proxy receives the absolute url
+        let proxy = serve(|head| {
+            if head.starts_with("GET http://cask.invalid/file ") {
+                response("200 OK", &["Content-Length: 5"], b"hello")
+            } else {
+                response("400 Bad Request", &["Content-Length: 0"], b"")
+            }
+        })
+        .await;
+
+        let options = DownloadOption {
+            http_proxy: Some(proxy),
+            ..Default::default()
+        };
+
+        let body = fetch("http://cask.invalid/file", &options).await.unwrap();
+
+        assert_eq!(body, b"hello");
+
+        // connect to the host directly
+        let r = fetch(
+            "http://cask.invalid/file",
+            &DownloadOption {
+                no_proxy: Some("localhost,.invalid".to_string()),
+                ..options
+            },
+        )
+        .await;
+
+        assert!(r.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_with_invalid_ca_file() {
+        let ca_file = env::temp_dir().join("cask_test_invalid_ca_file.pem");
+
+        fs::write(
+            &ca_file,
+            "-----BEGIN CERTIFICATE-----\ninvalid\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+
+        let r = fetch(
+            "http://cask.invalid/file",
+            &DownloadOption {
+                ca_file: Some(ca_file.clone()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(r.unwrap_err().to_string().contains("invalid CA file"));
+
+        fs::remove_file(&ca_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_without_content_length() {
+        let server = serve(|_| {
+            response(
+                "200 OK",
+                &["Transfer-Encoding: chunked"],
+                b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+            )
+        })
+        .await;
+
+        let url = format!("{}/file", server);
+        let dest = env::temp_dir().join("cask_test_download_without_content_length");
+
+        download(&url, &dest).await.unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"hello world");
+
+        // exceed the max size
+        let r = download_with(
+            &url,
+            &dest,
+            &DownloadOption {
+                max_size: Some(8),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(r.unwrap_err().to_string().contains("max size"));
+        assert!(!env::temp_dir()
+            .join("cask_test_download_without_content_length.part")
+            .exists());
+
+        fs::remove_file(&dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backends_with_https() {
+        let (server, ca) = serve_tls(|head| {
+            if head.starts_with("GET /file ") {
+                response("200 OK", &["Content-Length: 5"], b"hello")
+            } else {
+                response("404 Not Found", &["Content-Length: 0"], b"")
+            }
+        })
+        .await;
+
+        let ca_file = env::temp_dir().join("cask_test_backends_ca.pem");
+
+        fs::write(&ca_file, ca).unwrap();
+
+        let url = format!("{}/file", server);
+
+        let options = DownloadOption {
+            ca_file: Some(ca_file.clone()),
+            ..Default::default()
+        };
+
+        for (name, backend) in backends() {
+            // the certificate is not trusted without the CA
+            assert!(
+                http::fetch(backend, &url, &DownloadOption::default())
+                    .await
+                    .is_err(),
+                "{}",
+                name
+            );
+
+            assert_eq!(
+                http::fetch(backend, &url, &options).await.unwrap(),
+                b"hello",
+                "{}",
+                name
+            );
+
+            let dest = env::temp_dir().join(format!("cask_test_backends_with_https_{}", name));
+
+            http::download(backend, &url, &dest, &options)
+                .await
+                .unwrap();
+
+            assert_eq!(fs::read(&dest).unwrap(), b"hello", "{}", name);
+
+            fs::remove_file(&dest).unwrap();
+        }
+
+        fs::remove_file(&ca_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let server = serve(|head| {
+            if head.starts_with("GET /Cask.toml ") {
+                response("200 OK", &["Content-Length: 5"], b"hello")
+            } else {
+                response("404 Not Found", &["Content-Length: 0"], b"")
+            }
+        })
+        .await;
+
+        let body = fetch(&format!("{}/Cask.toml", server), &DownloadOption::default())
+            .await
+            .unwrap();
+
+        assert_eq!(body, b"hello");
+
+        assert!(
+            fetch(&format!("{}/not_exist", server), &DownloadOption::default())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,12 +1,106 @@
 #![deny(warnings)]
 
+mod checksum;
+mod http;
+#[cfg(feature = "openssl")]
+mod openssl;
+mod retry;
+#[cfg(feature = "rustls")]
 mod rustls;
 
 use core::result::Result;
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use eyre::Report;
 
+pub use checksum::{Algorithm, Checksum, ChecksumMismatchError};
+
+// prefer rustls if both of the backends are enabled
+#[cfg(feature = "rustls")]
+const BACKEND: http::Backend = rustls::client_builder;
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+const BACKEND: http::Backend = openssl::client_builder;
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+compile_error!("either the feature \"rustls\" or \"openssl\" must be enabled");
+
+#[derive(Default)]
+pub struct DownloadOption {
+    pub authorization: Option<String>, // The value of Authorization header. It will not be printed
+    pub retries: u32, // The max times to retry if download fail with the transient error
+    pub retry_delay: Duration, // The delay before the first retry, it's doubled after each attempt
+    pub max_retry_delay: Duration, // The max delay between attempts
+    pub http_proxy: Option<String>, // The proxy for http request
+    pub https_proxy: Option<String>, // The proxy for https request
+    pub no_proxy: Option<String>, // The comma-separated hosts that do not use the proxy
+    pub ca_file: Option<PathBuf>, // The PEM file of the extra trusted root certificates
+    pub max_size: Option<u64>, // The max bytes of the file, guard against the runaway response
+    pub checksum: Option<Checksum>, // Verify the file while downloading if provided
+}
+
 pub async fn download(url: &str, filepath: &Path) -> Result<(), Report> {
-    rustls::download(url, filepath).await
+    download_with(url, filepath, &DownloadOption::default()).await
+}
+
+pub async fn download_with(
+    url: &str,
+    filepath: &Path,
+    options: &DownloadOption,
+) -> Result<(), Report> {
+    let mut attempt: u32 = 0;
+
+    loop {
+        let err = match http::download(BACKEND, url, filepath, options).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        let delay = match retry::get_delay(&err, attempt, options) {
+            Some(d) => d,
+            None => return Err(err),
+        };
+
+        attempt += 1;
+
+        eprintln!(
+            "{:#}. Retry in {:.1}s ({}/{})",
+            err,
+            delay.as_secs_f64(),
+            attempt,
+            options.retries
+        );
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// download the file from the urls in order until one of them succeeds.
+// each url has its own option, eg. the credential of the host
+pub async fn download_from_mirrors(
+    mirrors: &[(String, DownloadOption)],
+    filepath: &Path,
+) -> Result<(), Report> {
+    let mut last_err = eyre::format_err!("no url to download");
+
+    for (index, (url, options)) in mirrors.iter().enumerate() {
+        match download_with(url, filepath, options).await {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                if let Some((next_url, _)) = mirrors.get(index + 1) {
+                    eprintln!("{:#}. Try the next mirror '{}'", e, next_url);
+                }
+
+                last_err = e
+            }
+        };
+    }
+
+    Err(last_err)
+}
+
+// fetch the small file into memory without progress bar
+pub async fn fetch(url: &str, options: &DownloadOption) -> Result<Vec<u8>, Report> {
+    http::fetch(BACKEND, url, options).await
 }
@@ -0,0 +1,10 @@
+#![deny(warnings)]
+
+use reqwest::ClientBuilder;
+
+// the client with native-tls, it uses OpenSSL on Linux, Security.framework on macOS and SChannel on Windows
+// the rustls backend is preferred if both of them enabled
+#[cfg_attr(feature = "rustls", allow(dead_code))]
+pub(crate) fn client_builder() -> ClientBuilder {
+    ClientBuilder::new().use_native_tls()
+}
@@ -0,0 +1,183 @@
+#![deny(warnings)]
+
+use std::time::{Duration, SystemTime};
+
+use eyre::Report;
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use thiserror::Error;
+
+use crate::DownloadOption;
+
+#[derive(Error, Debug)]
+#[error("Download {url} fail with http code {status}")]
+pub struct HttpStatusError {
+    pub url: String,
+    pub status: StatusCode,
+    pub retry_after: Option<Duration>, // The delay that the server asks for
+}
+
+impl HttpStatusError {
+    pub fn new(url: &str, res: &Response) -> HttpStatusError {
+        let status = res.status();
+
+        // only honour Retry-After of the rate limit and unavailable response
+        let retry_after = if status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::SERVICE_UNAVAILABLE
+        {
+            res.headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after)
+        } else {
+            None
+        };
+
+        HttpStatusError {
+            url: url.to_string(),
+            status,
+            retry_after,
+        }
+    }
+}
+
+// parse the value of Retry-After header. eg. 120 or Wed, 21 Oct 2015 07:28:00 GMT
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value.trim()).ok()?;
+
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+// whether the error is transient and the download can be retried
+fn is_retryable(error: &Report) -> bool {
+    if let Some(e) = error.downcast_ref::<HttpStatusError>() {
+        return matches!(
+            e.status,
+            StatusCode::REQUEST_TIMEOUT
+                | StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::INTERNAL_SERVER_ERROR
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return e.is_connect() || e.is_timeout() || e.is_request() || e.is_body();
+    }
+
+    false
+}
+
+// get the delay before next attempt, returns None if it should not retry.
+// the delay is doubled after each attempt with jitter, unless the server asks for a delay
+pub(crate) fn get_delay(
+    error: &Report,
+    attempt: u32,
+    options: &DownloadOption,
+) -> Option<Duration> {
+    if attempt >= options.retries || !is_retryable(error) {
+        return None;
+    }
+
+    if let Some(retry_after) = error
+        .downcast_ref::<HttpStatusError>()
+        .and_then(|e| e.retry_after)
+    {
+        // give up instead of retrying earlier than the server asks for
+        return if retry_after <= options.max_retry_delay {
+            Some(retry_after)
+        } else {
+            None
+        };
+    }
+
+    let delay = options
+        .retry_delay
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(options.max_retry_delay);
+
+    // the random delay between half and full to avoid retrying at the same time
+    let half = delay / 2;
+
+    Some(half + half.mul_f64(fastrand::f64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use crate::{
+        retry::{get_delay, parse_retry_after, HttpStatusError},
+        DownloadOption,
+    };
+
+    fn status_error(status: StatusCode, retry_after: Option<Duration>) -> eyre::Report {
+        eyre::Report::new(HttpStatusError {
+            url: "https://example.com".to_string(),
+            status,
+            retry_after,
+        })
+    }
+
+    #[test]
+    fn test_get_delay() {
+        let options = DownloadOption {
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+            max_retry_delay: Duration::from_secs(3),
+            ..Default::default()
+        };
+
+        let e = status_error(StatusCode::BAD_GATEWAY, None);
+
+        let delay = get_delay(&e, 0, &options).unwrap();
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+
+        let delay = get_delay(&e, 2, &options).unwrap();
+        assert!(delay >= Duration::from_millis(1500) && delay <= Duration::from_secs(3));
+
+        assert_eq!(get_delay(&e, 3, &options), None);
+
+        assert_eq!(
+            get_delay(&status_error(StatusCode::NOT_FOUND, None), 0, &options),
+            None
+        );
+        assert_eq!(get_delay(&eyre::format_err!("io error"), 0, &options), None);
+
+        assert_eq!(
+            get_delay(
+                &status_error(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(2))),
+                0,
+                &options
+            ),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            get_delay(
+                &status_error(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(60))),
+                0,
+                &options
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("invalid"), None);
+    }
+}
@@ -23,11 +23,11 @@ pub(crate) fn extract<R: Read>(
 
     let target_file_path = format!("{}/{}", folder, filename).replace("//", "/");
 
+    let re = Regex::new(r"^GNUSparseFile\.\d+/").unwrap();
+
     for mut entry in files {
         let file_path = entry.path()?;
 
-        let re = Regex::new(r"^GNUSparseFile\.\d+/").unwrap();
-
         // GNUSparseFile.0/gpm
         // ./gpm
         // /gpm
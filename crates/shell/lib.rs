@@ -4,15 +4,20 @@ use eyre::Report;
 
 use std::{
     collections::HashMap,
-    io,
+    fs,
+    io::{self, Read, Write},
     path::Path,
-    process::{Command as ChildProcess, Stdio},
+    process::{Child, Command as ChildProcess, ExitStatus, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
 };
 
 pub enum Output<'a> {
     Writer(&'a mut dyn io::Write), // write command output to writer
-    Inherit,                       // inherit stdout/stderr from parent process
-    None,                          // do none output anything
+    Tee(&'a fs::File), // write command output to file, and print it to stderr of parent process
+    Inherit,           // inherit stdout/stderr from parent process
+    None,              // do none output anything
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
@@ -21,17 +26,16 @@ pub enum Terminal {
     PowerShell,
     Sh,
     Bash,
+    Zsh,
+    Fish,
+    Pwsh,    // PowerShell Core
+    Nushell, // nu
 }
 
-pub fn run_with(
-    terminal: Terminal,
-    cwd: &Path,
-    command: &str,
-    output: &mut Output,
-    envs: HashMap<String, String>,
-) -> Result<(), Report> {
-    let commands: Vec<&str> = {
-        match terminal {
+impl Terminal {
+    // the program and arguments to run the command
+    fn commands(&self) -> Vec<&str> {
+        match self {
             Terminal::Cmd => vec!["cmd.exe", "--%", "/c"],
             Terminal::PowerShell => vec![
                 "powershell.exe",
@@ -42,8 +46,85 @@ pub fn run_with(
             ],
             Terminal::Sh => vec!["sh", "-c"],
             Terminal::Bash => vec!["bash", "-c"],
+            Terminal::Zsh => vec!["zsh", "-c"],
+            Terminal::Fish => vec!["fish", "-c"],
+            Terminal::Pwsh => vec![
+                "pwsh",
+                "-NoLogo",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+            ],
+            Terminal::Nushell => vec!["nu", "-c"],
         }
-    };
+    }
+
+    // whether the program of terminal can be found in $PATH
+    pub fn is_available(&self) -> bool {
+        which::which(self.commands()[0]).is_ok()
+    }
+}
+
+// How long to wait for the output after the command exited.
+// the background process of command may hold the output and never close it
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+// read the output of child process in the background, so that it will not block the child process
+fn read_chunks(mut reader: impl Read + Send + 'static, sender: mpsc::Sender<Vec<u8>>) {
+    thread::spawn(move || {
+        let mut buf = [0; 8192];
+
+        while let Ok(n) = reader.read(&mut buf) {
+            if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn write_output(output: &mut Output, chunk: &[u8]) -> io::Result<()> {
+    match output {
+        Output::Writer(w) => w.write_all(chunk),
+        Output::Tee(mut file) => {
+            file.write_all(chunk)?;
+            io::stderr().write_all(chunk)
+        }
+        _ => Ok(()),
+    }
+}
+
+// kill the command and the processes it started
+fn kill(child: &mut Child) {
+    // the child is the leader of its process group
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+
+    child.kill().ok();
+    child.wait().ok();
+}
+
+pub fn run_with(
+    terminal: Terminal,
+    cwd: &Path,
+    command: &str,
+    output: &mut Output,
+    envs: HashMap<String, String>,
+) -> Result<(), Report> {
+    run_with_timeout(terminal, cwd, command, output, envs, None)
+}
+
+// run the command, the command and the processes it started will be killed if it does not exit in time
+pub fn run_with_timeout(
+    terminal: Terminal,
+    cwd: &Path,
+    command: &str,
+    output: &mut Output,
+    envs: HashMap<String, String>,
+    timeout: Option<Duration>,
+) -> Result<(), Report> {
+    let commands = terminal.commands();
 
     let cmd = commands.first().unwrap();
     let mut args = commands.clone().split_off(1);
@@ -58,8 +139,16 @@ pub fn run_with(
 
     ps.envs(envs);
 
+    // so that the processes started by command can be killed together
+    #[cfg(unix)]
+    if timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+
+        ps = ps.process_group(0);
+    }
+
     match &output {
-        Output::Writer(_) => {
+        Output::Writer(_) | Output::Tee(_) => {
             ps = ps.stdout(Stdio::piped()).stderr(Stdio::piped());
         }
         Output::Inherit => ps = ps.stdout(Stdio::inherit()).stderr(Stdio::inherit()),
@@ -71,23 +160,73 @@ pub fn run_with(
         Err(e) => Err(eyre::format_err!("{}", e)),
     }?;
 
-    if let Output::Writer(r) = output {
-        io::copy(&mut child.stdout.take().unwrap(), r)?;
-        io::copy(&mut child.stderr.take().unwrap(), r)?;
-    };
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+
+    if let Some(stdout) = child.stdout.take() {
+        read_chunks(stdout, sender.clone());
+    }
+
+    if let Some(stderr) = child.stderr.take() {
+        read_chunks(stderr, sender.clone());
+    }
+
+    drop(sender);
+
+    let start_at = Instant::now();
+    let mut exited: Option<(ExitStatus, Instant)> = None;
 
-    match child.wait() {
-        Ok(state) => {
-            if state.success() {
-                Ok(())
-            } else {
-                Err(eyre::format_err!(
-                    "exit code: {}",
-                    state.code().unwrap_or(1),
-                ))
+    loop {
+        let is_drained = match receiver.recv_timeout(Duration::from_millis(50)) {
+            Ok(chunk) => {
+                write_output(output, &chunk)?;
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                if exited.is_none() {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                true
+            }
+        };
+
+        if exited.is_none() {
+            if let Some(state) = child.try_wait()? {
+                exited = Some((state, Instant::now()));
+            }
+        }
+
+        let is_timeout = timeout.map(|t| start_at.elapsed() >= t).unwrap_or(false);
+
+        match exited {
+            // do not wait for the output forever, the background process may hold it
+            Some((_, exited_at)) => {
+                if is_drained || is_timeout || exited_at.elapsed() >= OUTPUT_DRAIN_TIMEOUT {
+                    break;
+                }
+            }
+            None => {
+                if is_timeout {
+                    kill(&mut child);
+
+                    return Err(eyre::format_err!(
+                        "timeout after {:.2?}",
+                        timeout.unwrap_or_default()
+                    ));
+                }
             }
         }
-        Err(e) => Err(eyre::format_err!("{}", e)),
+    }
+
+    let state = exited.map(|(state, _)| state).unwrap();
+
+    if state.success() {
+        Ok(())
+    } else {
+        Err(eyre::format_err!(
+            "exit code: {}",
+            state.code().unwrap_or(1)
+        ))
     }
 }
 
@@ -110,8 +249,10 @@ pub fn run(
 mod tests {
     use std::collections::HashMap;
     use std::env;
+    use std::fs;
+    use std::time::{Duration, Instant};
 
-    use crate::{run, run_with, Output, Terminal};
+    use crate::{run, run_with, run_with_timeout, Output, Terminal};
 
     #[test]
     fn test_echo() {
@@ -276,4 +417,163 @@ mod tests {
 
         assert!(result.contains("hello world"))
     }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_with_timeout() {
+        let cwd = env::current_dir().unwrap();
+
+        let start_at = Instant::now();
+
+        let r = run_with_timeout(
+            Terminal::Sh,
+            &cwd,
+            "sleep 5",
+            &mut Output::None,
+            HashMap::from([]),
+            Some(Duration::from_millis(200)),
+        );
+
+        assert!(r.is_err());
+        assert!(start_at.elapsed() < Duration::from_secs(5));
+
+        run_with_timeout(
+            Terminal::Sh,
+            &cwd,
+            "exit 0",
+            &mut Output::None,
+            HashMap::from([]),
+            Some(Duration::from_secs(5)),
+        )
+        .unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_with_tee() {
+        let cwd = env::current_dir().unwrap();
+        let log_file_path = env::temp_dir().join("shell_test_with_tee.log");
+
+        let file = fs::File::create(&log_file_path).unwrap();
+
+        run_with(
+            Terminal::Sh,
+            &cwd,
+            "echo 'hello stdout' && echo 'hello stderr' >&2",
+            &mut Output::Tee(&file),
+            HashMap::from([]),
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&log_file_path).unwrap();
+
+        fs::remove_file(&log_file_path).ok();
+
+        assert!(content.contains("hello stdout"));
+        assert!(content.contains("hello stderr"));
+    }
+
+    #[test]
+    fn test_with_other_terminals() {
+        let cwd = env::current_dir().unwrap();
+
+        for terminal in [
+            Terminal::Zsh,
+            Terminal::Fish,
+            Terminal::Pwsh,
+            Terminal::Nushell,
+        ] {
+            // skip the terminal that not installed
+            if !terminal.is_available() {
+                continue;
+            }
+
+            let mut buf = Vec::new();
+
+            run_with(
+                terminal,
+                &cwd,
+                r#"echo "hello""#,
+                &mut Output::Writer(&mut buf),
+                HashMap::from([]),
+            )
+            .unwrap();
+
+            assert_eq!(std::str::from_utf8(&buf).unwrap().trim(), "hello")
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_is_available() {
+        assert!(Terminal::Sh.is_available());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_with_background_process() {
+        let cwd = env::current_dir().unwrap();
+
+        let start_at = Instant::now();
+        let mut buf = Vec::new();
+
+        // the background process holds the output, but it should not block
+        run_with_timeout(
+            Terminal::Sh,
+            &cwd,
+            "sleep 30 & echo 'started'",
+            &mut Output::Writer(&mut buf),
+            HashMap::from([]),
+            Some(Duration::from_secs(20)),
+        )
+        .unwrap();
+
+        assert!(start_at.elapsed() < Duration::from_secs(20));
+        assert_eq!(std::str::from_utf8(&buf).unwrap().trim(), "started");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_kill_process_group_on_timeout() {
+        let cwd = env::current_dir().unwrap();
+        let pid_file = env::temp_dir().join("shell_test_kill_process_group.pid");
+
+        fs::remove_file(&pid_file).ok();
+
+        let r = run_with_timeout(
+            Terminal::Sh,
+            &cwd,
+            &format!("sleep 30 & echo $! > '{}'; wait", pid_file.display()),
+            &mut Output::None,
+            HashMap::from([]),
+            Some(Duration::from_millis(500)),
+        );
+
+        assert!(r.is_err());
+
+        let pid = fs::read_to_string(&pid_file).unwrap();
+
+        fs::remove_file(&pid_file).ok();
+
+        // the background process has been killed too
+        let mut is_alive = true;
+
+        for _ in 0..20 {
+            is_alive = std::process::Command::new("kill")
+                .arg("-0")
+                .arg(pid.trim())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .unwrap()
+                .success();
+
+            if !is_alive {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(!is_alive);
+    }
 }
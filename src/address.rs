@@ -0,0 +1,362 @@
+#![deny(warnings)]
+
+use std::path::PathBuf;
+
+use eyre::Report;
+use url::Url;
+
+// The address of package
+#[derive(Debug, PartialEq, Eq)]
+pub struct Address {
+    pub name: String, // The stable package name. eg. github.com/axetroy/gpm.rs
+    pub url: String,  // The git url to fetch the formula
+}
+
+// trim the '.git' suffix and slash of path
+fn normalize_path(path: &str) -> &str {
+    let p = path.trim_matches('/');
+
+    p.strip_suffix(".git").unwrap_or(p).trim_end_matches('/')
+}
+
+fn get_name(host: &str, path: &str) -> String {
+    format!("{}/{}", host.to_lowercase(), normalize_path(path))
+}
+
+// scp-like address. eg. git@github.com:axetroy/gpm.rs.git
+fn parse_scp_like(address: &str) -> Option<Address> {
+    let (user_and_host, path) = address.split_once(':')?;
+
+    if path.starts_with("//") {
+        return None;
+    }
+
+    let (user, host) = user_and_host.split_once('@')?;
+
+    let is_valid = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+    };
+
+    if !is_valid(user) || !is_valid(host) || normalize_path(path).is_empty() {
+        return None;
+    }
+
+    Some(Address {
+        name: get_name(host, path),
+        url: address.to_string(),
+    })
+}
+
+// parse the address of package. eg.
+// https://github.com/axetroy/gpm.rs
+// ssh://git@github.com/axetroy/gpm.rs.git
+// git@github.com:axetroy/gpm.rs.git
+// returns None if the address is a package name. eg. github.com/axetroy/gpm.rs
+pub fn parse(address: &str) -> Result<Option<Address>, Report> {
+    if let Some(addr) = parse_scp_like(address) {
+        return Ok(Some(addr));
+    }
+
+    if let Ok(u) = Url::parse(address) {
+        let scheme = u.scheme();
+
+        return match scheme {
+            "http" | "https" | "ssh" | "git+ssh" => {
+                let host = u.host_str().ok_or_else(|| {
+                    eyre::format_err!("can not get host of package address '{}'", address)
+                })?;
+
+                Ok(Some(Address {
+                    name: get_name(host, u.path()),
+                    url: address.to_string(),
+                }))
+            }
+            _ => Err(eyre::format_err!(
+                "Not support the protocol '{}' of package address.",
+                scheme
+            )),
+        };
+    }
+
+    Ok(None)
+}
+
+// split the subpath of formula from address. eg.
+// github.com/org/tools//formulas/foo -> (github.com/org/tools, formulas/foo)
+// github.com/org/tools#foo -> (github.com/org/tools, foo)
+// https://github.com/org/tools.git//formulas/foo -> (https://github.com/org/tools.git, formulas/foo)
+pub fn split_subpath(address: &str) -> Result<(&str, Option<String>), Report> {
+    let (addr, subpath) = if let Some((addr, fragment)) = address.rsplit_once('#') {
+        (addr, fragment)
+    } else {
+        // skip the '//' of scheme
+        let start = address.find("://").map(|i| i + 3).unwrap_or(0);
+
+        match address[start..].find("//") {
+            Some(i) => (&address[..start + i], &address[start + i + 2..]),
+            None => return Ok((address, None)),
+        }
+    };
+
+    let subpath = subpath.replace('\\', "/");
+    let subpath = subpath.trim_matches('/');
+
+    if subpath.is_empty() {
+        return Ok((addr, None));
+    }
+
+    if subpath.split('/').any(|p| p == ".." || p == ".") {
+        return Err(eyre::format_err!(
+            "invalid subpath '{}' of package address '{}'",
+            subpath,
+            address
+        ));
+    }
+
+    Ok((addr, Some(subpath.to_string())))
+}
+
+// join the address and the subpath of formula
+pub fn join_subpath(address: &str, subpath: Option<&str>) -> String {
+    match subpath {
+        Some(p) => format!("{}//{}", address, p),
+        None => address.to_string(),
+    }
+}
+
+// get the local path of address. eg.
+// ./path/to/repo
+// /srv/git/tool.git
+// file:///srv/git/tool.git
+// returns None if the address is not a local path
+pub fn get_local_path(address: &str) -> Option<PathBuf> {
+    if address.starts_with("file://") {
+        return Url::parse(address).ok()?.to_file_path().ok();
+    }
+
+    if let Some(p) = address.strip_prefix("~/") {
+        return dirs::home_dir().map(|h| h.join(p));
+    }
+
+    let is_windows_absolute = {
+        let chars: Vec<char> = address.chars().take(3).collect();
+
+        chars.len() == 3
+            && chars[0].is_ascii_alphabetic()
+            && chars[1] == ':'
+            && (chars[2] == '\\' || chars[2] == '/')
+    };
+
+    if address == "."
+        || address == ".."
+        || address.starts_with("./")
+        || address.starts_with("../")
+        || address.starts_with(".\\")
+        || address.starts_with("..\\")
+        || address.starts_with('/')
+        || address.starts_with("\\\\")
+        || is_windows_absolute
+    {
+        return Some(PathBuf::from(address));
+    }
+
+    None
+}
+
+// make the path of local file to be recognized as local address. eg. Cask.toml -> ./Cask.toml
+pub fn to_local_address(path: &str) -> String {
+    if get_local_path(path).is_some() {
+        path.to_string()
+    } else {
+        format!("./{}", path)
+    }
+}
+
+// get the stable package name of address, return the address itself if it's already a name
+pub fn get_package_name(address: &str) -> String {
+    match parse(address) {
+        Ok(Some(addr)) => addr.name,
+        _ => normalize_path(address).to_string(),
+    }
+}
+
+// whether the address is fetched with ssh
+pub fn is_ssh(address: &str) -> bool {
+    if parse_scp_like(address).is_some() {
+        return true;
+    }
+
+    matches!(Url::parse(address), Ok(u) if u.scheme() == "ssh" || u.scheme() == "git+ssh")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::address::{self, Address};
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            address::parse("https://github.com/axetroy/gpm.rs").unwrap(),
+            Some(Address {
+                name: "github.com/axetroy/gpm.rs".to_string(),
+                url: "https://github.com/axetroy/gpm.rs".to_string(),
+            })
+        );
+
+        assert_eq!(
+            address::parse("https://GitHub.com/axetroy/gpm.rs.git/").unwrap(),
+            Some(Address {
+                name: "github.com/axetroy/gpm.rs".to_string(),
+                url: "https://GitHub.com/axetroy/gpm.rs.git/".to_string(),
+            })
+        );
+
+        assert_eq!(
+            address::parse("ssh://git@github.com:22/axetroy/gpm.rs.git").unwrap(),
+            Some(Address {
+                name: "github.com/axetroy/gpm.rs".to_string(),
+                url: "ssh://git@github.com:22/axetroy/gpm.rs.git".to_string(),
+            })
+        );
+
+        assert_eq!(
+            address::parse("git@github.com:axetroy/gpm.rs.git").unwrap(),
+            Some(Address {
+                name: "github.com/axetroy/gpm.rs".to_string(),
+                url: "git@github.com:axetroy/gpm.rs.git".to_string(),
+            })
+        );
+
+        assert_eq!(
+            address::parse("git@git.example.com:/srv/tools/gpm").unwrap(),
+            Some(Address {
+                name: "git.example.com/srv/tools/gpm".to_string(),
+                url: "git@git.example.com:/srv/tools/gpm".to_string(),
+            })
+        );
+
+        assert_eq!(address::parse("github.com/axetroy/gpm.rs").unwrap(), None);
+
+        assert!(address::parse("ftp://github.com/axetroy/gpm.rs").is_err());
+    }
+
+    #[test]
+    fn test_split_subpath() {
+        assert_eq!(
+            address::split_subpath("github.com/org/tools//formulas/foo").unwrap(),
+            ("github.com/org/tools", Some("formulas/foo".to_string()))
+        );
+        assert_eq!(
+            address::split_subpath("github.com/org/tools#foo").unwrap(),
+            ("github.com/org/tools", Some("foo".to_string()))
+        );
+        assert_eq!(
+            address::split_subpath("https://github.com/org/tools.git//formulas/foo/").unwrap(),
+            (
+                "https://github.com/org/tools.git",
+                Some("formulas/foo".to_string())
+            )
+        );
+        assert_eq!(
+            address::split_subpath("git@github.com:org/tools.git//foo").unwrap(),
+            ("git@github.com:org/tools.git", Some("foo".to_string()))
+        );
+        assert_eq!(
+            address::split_subpath("https://github.com/org/tools").unwrap(),
+            ("https://github.com/org/tools", None)
+        );
+        assert_eq!(
+            address::split_subpath("github.com/org/tools").unwrap(),
+            ("github.com/org/tools", None)
+        );
+        assert!(address::split_subpath("github.com/org/tools//../foo").is_err());
+
+        assert_eq!(
+            address::join_subpath("github.com/org/tools", Some("formulas/foo")),
+            "github.com/org/tools//formulas/foo"
+        );
+
+        // the different forms of subpath refer to the same package
+        let normalize = |addr: &str| {
+            let (a, subpath) = address::split_subpath(addr).unwrap();
+            address::join_subpath(a, subpath.as_deref())
+        };
+
+        assert_eq!(
+            normalize("github.com/org/tools#foo"),
+            normalize("github.com/org/tools//foo")
+        );
+        assert_eq!(
+            normalize("github.com/org/tools#/foo/"),
+            "github.com/org/tools//foo"
+        );
+    }
+
+    #[test]
+    fn test_get_local_path() {
+        assert_eq!(
+            address::get_local_path("./path/to/repo"),
+            Some(PathBuf::from("./path/to/repo"))
+        );
+        assert_eq!(address::get_local_path("."), Some(PathBuf::from(".")));
+        assert_eq!(
+            address::get_local_path("C:\\path\\to\\repo"),
+            Some(PathBuf::from("C:\\path\\to\\repo"))
+        );
+        assert_eq!(address::get_local_path("github.com/axetroy/gpm.rs"), None);
+        assert_eq!(
+            address::get_local_path("git@github.com:axetroy/gpm.rs.git"),
+            None
+        );
+
+        #[cfg(unix)]
+        assert_eq!(
+            address::get_local_path("file:///srv/git/tool.git"),
+            Some(PathBuf::from("/srv/git/tool.git"))
+        );
+    }
+
+    #[test]
+    fn test_get_package_name() {
+        assert_eq!(
+            address::get_package_name("git@github.com:axetroy/gpm.rs.git"),
+            "github.com/axetroy/gpm.rs"
+        );
+        assert_eq!(
+            address::get_package_name("github.com/axetroy/gpm.rs"),
+            "github.com/axetroy/gpm.rs"
+        );
+    }
+
+    #[test]
+    fn test_is_ssh() {
+        assert!(address::is_ssh("git@github.com:axetroy/gpm.rs.git"));
+        assert!(address::is_ssh("ssh://git@github.com/axetroy/gpm.rs.git"));
+        assert!(!address::is_ssh("https://github.com/axetroy/gpm.rs"));
+        assert!(!address::is_ssh("github.com/axetroy/gpm.rs"));
+    }
+
+    #[test]
+    fn test_to_local_address() {
+        assert_eq!(address::to_local_address("Cask.toml"), "./Cask.toml");
+        assert_eq!(
+            address::to_local_address("formula/Cask.toml"),
+            "./formula/Cask.toml"
+        );
+        assert_eq!(address::to_local_address("./Cask.toml"), "./Cask.toml");
+        assert_eq!(address::to_local_address("../Cask.toml"), "../Cask.toml");
+        assert_eq!(
+            address::to_local_address("/tmp/Cask.toml"),
+            "/tmp/Cask.toml"
+        );
+
+        assert_eq!(
+            address::get_local_path(&address::to_local_address("Cask.toml")),
+            Some(PathBuf::from("./Cask.toml"))
+        );
+    }
+}
@@ -1,6 +1,6 @@
 #![deny(warnings)]
 
-use crate::formula;
+use crate::{config, credentials, formula, policy};
 
 use std::env;
 use std::fs;
@@ -98,6 +98,110 @@ impl Cask {
         self.root_dir().join("build-in")
     }
 
+    pub fn cache_dir(&self) -> PathBuf {
+        self.root_dir().join("cache")
+    }
+
+    pub fn tags_cache_dir(&self) -> PathBuf {
+        self.cache_dir().join("tags")
+    }
+
+    // the folder of download cache, it may locate in the shared volume
+    pub fn downloads_cache_dir(&self) -> Result<PathBuf, Report> {
+        Ok(self
+            .config()?
+            .downloads_dir()
+            .unwrap_or_else(|| self.cache_dir().join("downloads")))
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.root_dir().join("config.toml")
+    }
+
+    pub fn config(&self) -> Result<config::Config, Report> {
+        config::load(&self.config_file())
+    }
+
+    // the policy of user, the system policy is loaded too
+    pub fn policy_file(&self) -> PathBuf {
+        self.root_dir().join("policy.toml")
+    }
+
+    pub fn logs_dir(&self) -> PathBuf {
+        self.root_dir().join("logs")
+    }
+
+    // the output of the hooks of package, it's kept after uninstall.
+    // the name is readable, and the hash of package name makes it unique. eg. github.com_axetroy_dvm_0123456789abcdef.log
+    pub fn hook_log_file(&self, package_name: &str) -> PathBuf {
+        let hash_of_package = {
+            let mut hasher = Sha256::new();
+
+            hasher.update(package_name);
+            format!("{:x}", hasher.finalize())
+        };
+
+        let file_name = package_name.replace(['/', '\\', ':'], "_");
+
+        self.logs_dir()
+            .join(format!("{}_{}.log", file_name, &hash_of_package[..16]))
+    }
+
+    // the hash of hooks that user approved
+    pub fn approved_hooks_file(&self) -> PathBuf {
+        self.root_dir().join("approved_hooks.json")
+    }
+
+    pub fn policy(&self) -> Result<policy::Policies, Report> {
+        policy::load(self)
+    }
+
+    // get the git repository with the credential, proxy and certificates of remote
+    pub fn git(&self, url: &str) -> Result<git::Repository, Report> {
+        let mut repo = git::new(url)?;
+
+        if let Some(credential) = credentials::lookup(self, url)? {
+            repo = repo.with_authorization(&credential.authorization());
+        }
+
+        let config = self.config()?;
+
+        // keep the same proxy as the download for the http remote.
+        // leave the proxy of git config untouched if cask does not set any
+        if url.starts_with("http://") || url.starts_with("https://") {
+            if let Some(proxy) = config.proxy_for(url) {
+                repo = repo.with_proxy(&proxy);
+            } else if config.is_no_proxy_url(url) {
+                repo = repo.with_proxy("");
+            }
+        }
+
+        if let Some(ca_file) = config.ca_file() {
+            repo = repo.with_ca_file(&ca_file);
+        }
+
+        Ok(repo)
+    }
+
+    // get the download option with the credential of url and the retry policy
+    pub fn download_option(&self, url: &str) -> Result<downloader::DownloadOption, Report> {
+        let credential = credentials::lookup(self, url)?;
+        let config = self.config()?;
+
+        Ok(downloader::DownloadOption {
+            authorization: credential.map(|c| c.authorization()),
+            retries: config.retries(),
+            retry_delay: config.retry_delay(),
+            max_retry_delay: config.max_retry_delay(),
+            http_proxy: config.http_proxy(),
+            https_proxy: config.https_proxy(),
+            no_proxy: config.no_proxy(),
+            ca_file: config.ca_file(),
+            max_size: config.max_download_size(),
+            checksum: None,
+        })
+    }
+
     // the package folder
     pub fn package_dir(&self, package_name: &str) -> PathBuf {
         let hash_of_package = {
@@ -110,6 +214,11 @@ impl Cask {
         self.formula_dir().join(hash_of_package)
     }
 
+    // the formula repository of package, the hooks run in it
+    pub fn package_repository_dir(&self, package_name: &str) -> PathBuf {
+        self.package_dir(package_name).join("repository")
+    }
+
     pub fn package_bin_dir(&self, package_name: &str) -> PathBuf {
         self.package_dir(package_name).join("bin")
     }
@@ -165,3 +274,30 @@ impl Cask {
         Ok(list)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::cask;
+
+    #[test]
+    fn test_hook_log_file() {
+        let c = cask::new(&env::temp_dir().join("cask_test_hook_log_file"));
+
+        let file = c.hook_log_file("github.com/axetroy/dvm");
+
+        assert_eq!(file.parent().unwrap(), c.logs_dir());
+        assert!(file
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("github.com_axetroy_dvm_"));
+
+        // the names are the same after replacing the separators
+        assert_ne!(
+            c.hook_log_file("github.com/a_b/c"),
+            c.hook_log_file("github.com/a/b_c")
+        );
+    }
+}
@@ -0,0 +1,16 @@
+#![deny(warnings)]
+
+use crate::{cask, download_cache};
+
+use eyre::Report;
+
+pub async fn clear(cask: &cask::Cask) -> Result<(), Report> {
+    download_cache::clear(cask)?;
+
+    eprintln!(
+        "The download cache '{}' has been cleared!",
+        cask.downloads_cache_dir()?.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+#![deny(warnings)]
+
+use crate::{cask, download_cache};
+
+use chrono::prelude::*;
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+use tabled::{settings::Style, Table, Tabled};
+
+#[derive(Serialize, Deserialize, Debug, Tabled)]
+struct CacheInfo {
+    sha256: String,
+    size: u64,
+    #[tabled(display_with = "display_urls")]
+    urls: Vec<String>,
+    accessed_at: String,
+}
+
+fn display_urls(urls: &[String]) -> String {
+    urls.join("\n")
+}
+
+pub async fn list(cask: &cask::Cask, is_print_as_json: bool) -> Result<(), Report> {
+    let mut caches: Vec<CacheInfo> = vec![];
+
+    for entry in download_cache::list(cask)? {
+        let accessed_at: DateTime<Local> = entry.accessed_at.into();
+
+        caches.push(CacheInfo {
+            sha256: entry.sha256,
+            size: entry.size,
+            urls: entry.urls,
+            accessed_at: accessed_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        })
+    }
+
+    if is_print_as_json {
+        let serialized = serde_json::to_string(&caches).unwrap();
+        println!("{}", serialized);
+    } else {
+        let table = Table::new(&caches).with(Style::psql()).to_string();
+        print!("{}", table);
+    }
+
+    Ok(())
+}
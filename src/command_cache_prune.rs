@@ -0,0 +1,37 @@
+#![deny(warnings)]
+
+use crate::{cask, download_cache};
+
+use std::time::Duration;
+
+use eyre::Report;
+
+pub async fn prune(
+    cask: &cask::Cask,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> Result<(), Report> {
+    let config = cask.config()?;
+
+    // fallback to the limits of config file
+    let max_size = max_size.or_else(|| config.downloads_max_size());
+    let max_age = max_age.or_else(|| config.downloads_max_age());
+
+    if max_size.is_none() && max_age.is_none() {
+        return Err(eyre::format_err!(
+            "'--max-size' or '--max-age' required if the limit of download cache is not configured"
+        ));
+    }
+
+    let removed = download_cache::prune(cask, max_size, max_age)?;
+
+    let size: u64 = removed.iter().map(|e| e.size).sum();
+
+    eprintln!(
+        "Removed {} cached files, {} bytes freed",
+        removed.len(),
+        size
+    );
+
+    Ok(())
+}
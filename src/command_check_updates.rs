@@ -1,6 +1,6 @@
 #![deny(warnings)]
 
-use crate::{cask, command_install};
+use crate::{cask, command_install, hook_approval};
 use eyre::Report;
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug)]
 struct PackageInfo {
     name: String,
+    address: String,
+    formula_ref: Option<String>,
     bin: String,
     current_version: String,
     latest_version: String,
@@ -17,6 +19,8 @@ pub async fn check_updates(
     cask: &cask::Cask,
     is_check_only: bool,
     is_verbose: bool,
+    is_refresh: bool,
+    script_permission: hook_approval::Permission,
 ) -> Result<(), Report> {
     let mut packages: Vec<PackageInfo> = vec![];
 
@@ -31,7 +35,7 @@ pub async fn check_updates(
     for package in package_list {
         eprintln!("Checking {} for update...", package.package.name);
 
-        let latest_version_op = match package.get_latest_version() {
+        let latest_version_op = match package.get_latest_version(cask, is_refresh) {
             Ok(ver) => ver,
             Err(e) => {
                 eprintln!(
@@ -48,6 +52,9 @@ pub async fn check_updates(
 
         let latest_version_str = latest_version_op.unwrap();
 
+        let package_address = package.get_source_address();
+        let formula_ref = package.get_formula_ref().map(|s| s.to_string());
+
         let cask_info = match package.cask {
             Some(info) => info,
             None => {
@@ -83,6 +90,8 @@ pub async fn check_updates(
 
         if latest > current {
             packages.push(PackageInfo {
+                address: package_address,
+                formula_ref,
                 name: package.package.name,
                 bin: package.package.bin,
                 current_version: cask_info.version,
@@ -100,9 +109,12 @@ pub async fn check_updates(
         if !is_check_only {
             if let Err(e) = command_install::install(
                 cask,
-                &package.name,
+                &package.address,
                 Some(&package.latest_version),
+                package.formula_ref.as_deref(),
                 is_verbose,
+                false,
+                script_permission,
             )
             .await
             {
@@ -29,7 +29,7 @@ pub async fn clean(cask: &cask::Cask) -> Result<(), Report> {
             if version_dir.exists() {
                 for download_resource in fs::read_dir(version_dir)? {
                     let resource_file_path = download_resource?;
-                    fs::remove_file(&resource_file_path.path())?;
+                    fs::remove_file(resource_file_path.path())?;
 
                     clean_log(resource_file_path.path());
                 }
@@ -4,7 +4,7 @@ use crate::{cask, formula};
 
 use eyre::Report;
 
-pub async fn info(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
+pub async fn info(cask: &cask::Cask, package_name: &str, is_refresh: bool) -> Result<(), Report> {
     let packages = cask.list_formula()?;
 
     let package = packages
@@ -45,7 +45,7 @@ pub async fn info(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
 
         print!("{}", msg);
 
-        let remote_versions = &package_formula.get_versions()?;
+        let remote_versions = &package_formula.get_versions(cask, is_refresh)?;
 
         println!("Remote Versions:");
 
@@ -55,7 +55,7 @@ pub async fn info(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
 
         Ok(())
     } else {
-        let package_formula = formula::fetch(cask, package_name, true, false)?;
+        let package_formula = formula::fetch(cask, package_name, None, true, false).await?;
 
         let msg = format!(
             r#"{}
@@ -74,7 +74,7 @@ pub async fn info(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
 
         print!("{}", msg);
 
-        let remote_versions = &package_formula.get_versions()?;
+        let remote_versions = &package_formula.get_versions(cask, is_refresh)?;
 
         println!("Remote Versions:");
 
@@ -1,27 +1,36 @@
 #![deny(warnings)]
 
-use crate::{cask, formula, symlink, util::get_iso8601};
+use crate::{
+    cask, download_cache, formula, hook_approval, hooker, signature, symlink, util::get_iso8601,
+};
 
 use std::{
     fs,
     fs::File,
     io::Write,
     io::{self, Read},
+    path::Path,
 };
 
 use atty::{is, Stream};
 use eyre::Report;
 use is_executable::IsExecutable;
 use semver::Version;
-use sha2::{Digest, Sha256};
 
 pub async fn install(
     cask: &cask::Cask,
     package_name: &str,
     version: Option<&str>,
+    formula_ref: Option<&str>,
     is_verbose: bool,
+    is_refresh: bool,
+    script_permission: hook_approval::Permission,
 ) -> Result<(), Report> {
-    let package_formula = if !is(Stream::Stdin) {
+    let policy = cask.policy()?;
+
+    let package_formula = if package_name.is_empty() && !is(Stream::Stdin) {
+        policy.check_local("stdin")?;
+
         // Read Cask.toml from stdin
         // cat Cask.toml | cask install
         let mut buffer = Vec::new();
@@ -31,6 +40,8 @@ pub async fn install(
 
         let mut f: formula::Formula = toml::from_str(content.trim())?;
 
+        policy.check_package(&f.package.name)?;
+
         let cask_file_path = cask.formula_dir().join("Cask.toml");
         fs::write(&cask_file_path, content)?;
 
@@ -44,7 +55,7 @@ pub async fn install(
             return Err(eyre::format_err!("<PACKAGE> required"));
         }
 
-        formula::fetch(cask, package_name, false, is_verbose)?
+        formula::fetch(cask, package_name, formula_ref, false, is_verbose).await?
     };
 
     // detect binary name conflict
@@ -67,11 +78,9 @@ pub async fn install(
         }
     }
 
-    let hook_cwd = &cask
-        .package_dir(&package_formula.package.name)
-        .join("repository");
+    let hook_cwd = &package_formula.hook_dir(cask);
 
-    let remote_versions = package_formula.get_versions()?;
+    let remote_versions = package_formula.get_versions(cask, is_refresh)?;
 
     if remote_versions.is_empty() {
         return Err(eyre::format_err!(
@@ -109,14 +118,60 @@ pub async fn install(
         }
     }?;
 
-    if let Some(hook) = &package_formula.hook {
+    let download_target = &package_formula
+        .get_current_download_url(&download_version)?
+        .rewrite(&cask.config()?);
+
+    policy.check_install(
+        &package_formula.package.name,
+        download_target.checksum.is_some() || download_target.checksum_url.is_some(),
+        download_target.signature_url.is_some(),
+        package_formula.hook.is_some(),
+    )?;
+
+    // it's an update if the package has been installed
+    let previous_version = get_installed_version(cask, &package_formula.package.name);
+
+    let renderer_context = || match &previous_version {
+        Some(v) => package_formula.ger_update_renderer_context(&download_version, v),
+        None => package_formula.ger_renderer_context(&download_version),
+    };
+
+    let hook_run_option = |hook: &hooker::Hook| {
+        hooker::new_run_option(
+            cask,
+            hook,
+            &package_formula.package,
+            &download_version,
+            previous_version.as_deref(),
+        )
+    };
+
+    // the hooks run only if they are approved
+    let hook = match &package_formula.hook {
+        Some(hook) => hook_approval::check(
+            cask,
+            &package_formula.package.name,
+            hook,
+            renderer_context(),
+            script_permission,
+        )?
+        .then_some(hook),
+        None => None,
+    };
+
+    if let Some(hook) = hook {
         if !hook_cwd.exists() {
             fs::create_dir_all(hook_cwd)?;
         }
 
-        let renderer_context = &package_formula.ger_renderer_context(&download_version);
+        let run_option = hook_run_option(hook)?;
 
-        hook.run("preinstall", hook_cwd, renderer_context)?;
+        hook.run("preinstall", hook_cwd, renderer_context(), &run_option)?;
+
+        if previous_version.is_some() {
+            hook.run("preupdate", hook_cwd, renderer_context(), &run_option)?;
+        }
     }
 
     // init formula folder
@@ -124,27 +179,64 @@ pub async fn install(
 
     let package_dir = cask.package_dir(&package_formula.package.name);
 
-    let download_target = &package_formula.get_current_download_url(&download_version)?;
-
     let tar_file_path = cask
         .package_version_dir(&package_formula.package.name)
         .join(format!("{}{}", &download_version, download_target.ext));
 
-    downloader::download(&download_target.url, &tar_file_path).await?;
+    let checksum = get_checksum(cask, download_target, &download_version).await?;
 
-    if let Some(checksum) = &download_target.checksum {
-        let mut file = File::open(&tar_file_path)?;
-        let mut hasher = Sha256::new();
-        io::copy(&mut file, &mut hasher)?;
-        drop(file);
-        let hash = format!("{:x}", hasher.finalize());
-        if hash != *checksum {
-            fs::remove_file(tar_file_path)?;
-            return Err(eyre::format_err!(
-                "The file SHA256 is '{}' but expect '{}'",
-                hash,
+    // the cache is keyed by SHA256, other digests look up by url and verify the cached file
+    let cache_key = checksum
+        .as_ref()
+        .filter(|c| c.algorithm == downloader::Algorithm::Sha256)
+        .map(|c| c.hex());
+
+    // the cached file is always verified by its SHA256, and by the checksum of formula if provided
+    let cached_file =
+        download_cache::get(cask, &download_target.original_url, cache_key.as_deref()).filter(
+            |f| {
                 checksum
-            ));
+                    .as_ref()
+                    .is_none_or(|checksum| checksum.verify_file(f).is_ok())
+            },
+        );
+
+    if let Some(cached_file) = &cached_file {
+        eprintln!(
+            "Using the cached file of '{}'",
+            &download_target.original_url
+        );
+
+        fs::copy(cached_file, &tar_file_path)?;
+    } else {
+        let mut mirrors = vec![];
+
+        for url in download_target.urls() {
+            // the file is verified while downloading
+            let options = downloader::DownloadOption {
+                checksum: checksum.clone(),
+                ..cask.download_option(url)?
+            };
+
+            mirrors.push((url.to_string(), options));
+        }
+
+        downloader::download_from_mirrors(&mirrors, &tar_file_path).await?;
+    }
+
+    if let Some(signature_url) = &download_target.signature_url {
+        if let Err(e) =
+            verify_signature(cask, &package_formula, signature_url, &tar_file_path).await
+        {
+            fs::remove_file(&tar_file_path)?;
+            return Err(e);
+        }
+    }
+
+    if cached_file.is_none() {
+        // the cache is not required for install
+        if let Err(e) = cache_download(cask, &download_target.original_url, &tar_file_path) {
+            eprintln!("Warning: can not cache the downloaded file: {}", e);
         }
     }
 
@@ -197,34 +289,34 @@ pub async fn install(
 
         let mut formula_file = File::create(file_path)?;
 
+        let cask_info = formula::Cask {
+            name: package_formula.package.name.clone(),
+            created_at: get_iso8601(),
+            version: download_version.clone(),
+            repository: package_formula.repository.clone(),
+            subpath: package_formula.subpath.clone(),
+            formula_ref: package_formula.formula_ref.clone(),
+            formula_commit: package_formula.formula_commit.clone(),
+        };
+
         formula_file.write_all(
             format!(
-                r#"# The file is generated by Cask. DO NOT MODIFY IT.
-                [cask]
-                name = "{}"
-                created_at = "{}"
-                version = "{}"
-                repository = "{}"
-
-                "#,
-                package_formula.package.name,
-                get_iso8601(),
-                download_version,
-                package_formula.repository
+                "# The file is generated by Cask. DO NOT MODIFY IT.\n{}\n",
+                cask_info.to_toml()?
             )
-            .lines()
-            .map(|s| s.trim_start().to_owned())
-            .collect::<Vec<String>>()
-            .join("\n")
             .as_bytes(),
         )?;
         formula_file.write_all(package_formula.get_file_content().as_bytes())?;
     }
 
-    if let Some(hook) = &package_formula.hook {
-        let renderer_context = package_formula.ger_renderer_context(&download_version);
+    if let Some(hook) = hook {
+        let run_option = hook_run_option(hook)?;
+
+        hook.run("postinstall", hook_cwd, renderer_context(), &run_option)?;
 
-        hook.run("postinstall", hook_cwd, renderer_context)?;
+        if previous_version.is_some() {
+            hook.run("postupdate", hook_cwd, renderer_context(), &run_option)?;
+        }
     }
 
     eprintln!(
@@ -239,3 +331,96 @@ pub async fn install(
 
     Ok(())
 }
+
+// get the version of installed package
+fn get_installed_version(cask: &cask::Cask, package_name: &str) -> Option<String> {
+    let cask_file_path = cask.package_dir(package_name).join("Cask.toml");
+
+    if !cask_file_path.exists() {
+        return None;
+    }
+
+    formula::new(&cask_file_path, "")
+        .ok()?
+        .cask
+        .map(|c| c.version)
+}
+
+// get the checksum of formula, or look it up in the published checksum file
+async fn get_checksum(
+    cask: &cask::Cask,
+    download_target: &formula::DownloadTarget,
+    version: &str,
+) -> Result<Option<downloader::Checksum>, Report> {
+    if download_target.checksum.is_some() {
+        return Ok(download_target.checksum.clone());
+    }
+
+    let checksum_url = match &download_target.checksum_url {
+        Some(url) => url,
+        None => {
+            if download_target.is_checksum_missing {
+                eprintln!(
+                    "Warning: the checksum of version '{}' is not recorded in the formula, skip the verification",
+                    version
+                );
+            }
+
+            return Ok(None);
+        }
+    };
+
+    let filename = download_target.filename().ok_or_else(|| {
+        eyre::format_err!(
+            "can not get the file name of '{}'",
+            &download_target.original_url
+        )
+    })?;
+
+    let content = downloader::fetch(checksum_url, &cask.download_option(checksum_url)?).await?;
+
+    let checksum =
+        downloader::Checksum::from_checksum_file(&String::from_utf8_lossy(&content), &filename)?
+            .ok_or_else(|| {
+                eyre::format_err!(
+                    "can not found the checksum of '{}' in '{}'",
+                    filename,
+                    checksum_url
+                )
+            })?;
+
+    Ok(Some(checksum))
+}
+
+// verify the downloaded file with the detached signature before extraction
+async fn verify_signature(
+    cask: &cask::Cask,
+    package_formula: &formula::Formula,
+    signature_url: &str,
+    file: &Path,
+) -> Result<(), Report> {
+    let public_key = package_formula.package.public_key.as_ref().ok_or_else(|| {
+        eyre::format_err!(
+            "The package '{}' provides the signature but no public key",
+            &package_formula.package.name
+        )
+    })?;
+
+    let content = downloader::fetch(signature_url, &cask.download_option(signature_url)?).await?;
+
+    signature::verify_file(file, &String::from_utf8_lossy(&content), public_key)
+}
+
+fn cache_download(cask: &cask::Cask, url: &str, file: &Path) -> Result<(), Report> {
+    download_cache::put(cask, url, file)?;
+
+    let config = cask.config()?;
+    let max_size = config.downloads_max_size();
+    let max_age = config.downloads_max_age();
+
+    if max_size.is_some() || max_age.is_some() {
+        download_cache::prune(cask, max_size, max_age)?;
+    }
+
+    Ok(())
+}
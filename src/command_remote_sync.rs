@@ -48,7 +48,7 @@ pub fn sync(cask: &cask::Cask, is_verbose: bool) -> Result<(), Report> {
     } else {
         eprintln!("Pulling build-in formula...");
 
-        let client = git::new("https://github.com/cask-pkg/cask-core")?;
+        let client = cask.git("https://github.com/cask-pkg/cask-core")?;
 
         client.clone(
             &mirror_dir,
@@ -60,6 +60,7 @@ pub fn sync(cask: &cask::Cask, is_verbose: bool) -> Result<(), Report> {
                 single_branch: Some(true),
                 dissociate: Some(true),
                 filter: Some("tree:0".to_string()),
+                branch: None,
             },
         )?
     }
@@ -8,8 +8,8 @@ use crate::cask;
 use eyre::Report;
 use semver::Version;
 
-/// run the following command to show all build target
-/// rustc --print target-list
+// run the following command to show all build target
+// rustc --print target-list
 
 fn get_arch() -> String {
     #[cfg(target_arch = "arm")]
@@ -180,8 +180,8 @@ fn get_abi() -> Option<String> {
 }
 
 // get the latest version without 'v' prefix
-fn get_latest_release() -> Result<String, Report> {
-    let versions = git::new(env!("CARGO_PKG_REPOSITORY"))?.versions()?;
+fn get_latest_release(cask: &cask::Cask) -> Result<String, Report> {
+    let versions = cask.git(env!("CARGO_PKG_REPOSITORY"))?.versions()?;
 
     let err_can_not_found_release = eyre::format_err!("There is no one release of Cask");
 
@@ -194,8 +194,8 @@ fn get_latest_release() -> Result<String, Report> {
     Ok(latest_version.to_string())
 }
 
-pub async fn self_update(_cask: &cask::Cask) -> Result<(), Report> {
-    let latest_release = get_latest_release()?;
+pub async fn self_update(cask: &cask::Cask) -> Result<(), Report> {
+    let latest_release = get_latest_release(cask)?;
 
     let latest_remote_version = Version::parse(&latest_release)
         .map_err(|e| eyre::format_err!("parse latest version '{}' fail: {}", &latest_release, e))?;
@@ -230,9 +230,16 @@ pub async fn self_update(_cask: &cask::Cask) -> Result<(), Report> {
         &latest_release, filename,
     );
 
+    let resource_url = cask.config()?.rewrite_url(&resource_url);
+
     let resource_file_path = env::temp_dir().join(format!("{}-{}", &latest_release, filename));
 
-    downloader::download(&resource_url, &resource_file_path).await?;
+    downloader::download_with(
+        &resource_url,
+        &resource_file_path,
+        &cask.download_option(&resource_url)?,
+    )
+    .await?;
 
     #[cfg(unix)]
     let exe_name = env!("CARGO_BIN_NAME").to_string();
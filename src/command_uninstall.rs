@@ -1,12 +1,16 @@
 #![deny(warnings)]
 
-use crate::cask;
+use crate::{cask, hook_approval, hooker};
 
 use std::fs;
 
 use eyre::Report;
 
-pub async fn uninstall(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
+pub async fn uninstall(
+    cask: &cask::Cask,
+    package_name: &str,
+    script_permission: hook_approval::Permission,
+) -> Result<(), Report> {
     let packages = cask.list_formula()?;
 
     let package_formula = packages
@@ -17,6 +21,46 @@ pub async fn uninstall(cask: &cask::Cask, package_name: &str) -> Result<(), Repo
             eyre::format_err!("can not found the installed package '{}'", package_name)
         })?;
 
+    let version = package_formula
+        .cask
+        .as_ref()
+        .map(|c| c.version.clone())
+        .unwrap_or_default();
+
+    let hook_cwd = package_formula.hook_dir(cask);
+
+    // the hooks run only if they are approved.
+    // do not ask for consent if there is no script to run on uninstall
+    let hook = match &package_formula.hook {
+        Some(hook) if hook.has_script("preuninstall") || hook.has_script("postuninstall") => {
+            hook_approval::check(
+                cask,
+                &package_formula.package.name,
+                hook,
+                package_formula.ger_renderer_context(&version),
+                script_permission,
+            )?
+            .then_some(hook)
+        }
+        _ => None,
+    };
+
+    if let Some(hook) = hook {
+        if !hook_cwd.exists() {
+            fs::create_dir_all(&hook_cwd)?;
+        }
+
+        let run_option =
+            hooker::new_run_option(cask, hook, &package_formula.package, &version, None)?;
+
+        hook.run(
+            "preuninstall",
+            &hook_cwd,
+            package_formula.ger_renderer_context(&version),
+            &run_option,
+        )?;
+    }
+
     // remove symlink file
     if cfg!(unix) {
         let symlink_file = cask.bin_dir().join(&package_formula.package.bin);
@@ -35,6 +79,27 @@ pub async fn uninstall(cask: &cask::Cask, package_name: &str) -> Result<(), Repo
 
     fs::remove_dir_all(cask.package_dir(&package_formula.package.name))?;
 
+    // ask for consent again if the package is installed next time
+    hook_approval::revoke(cask, &package_formula.package.name)?;
+
+    // the package folder has been removed, the package is uninstalled even if the hook fails.
+    // so the error of postuninstall is only a warning
+    if let Some(hook) = hook {
+        let result = hooker::new_run_option(cask, hook, &package_formula.package, &version, None)
+            .and_then(|run_option| {
+                hook.run(
+                    "postuninstall",
+                    &cask.root_dir(),
+                    package_formula.ger_renderer_context(&version),
+                    &run_option,
+                )
+            });
+
+        if let Err(e) = result {
+            eprintln!("Warning: the postuninstall hook fails: {}", e);
+        }
+    }
+
     eprintln!(
         "The package '{}' has been uninstalled!",
         package_formula.package.name
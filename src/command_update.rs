@@ -1,6 +1,6 @@
 #![deny(warnings)]
 
-use crate::{cask, command_install, formula};
+use crate::{cask, command_install, formula, hook_approval};
 
 use eyre::Report;
 use semver::Version;
@@ -10,6 +10,8 @@ pub async fn update(
     package_name: &str,
     is_check_only: bool,
     is_verbose: bool,
+    is_refresh: bool,
+    script_permission: hook_approval::Permission,
 ) -> Result<(), Report> {
     let packages = cask.list_formula()?;
 
@@ -31,9 +33,16 @@ pub async fn update(
     let current = Version::parse(&cask_info.version)
         .map_err(|e| eyre::format_err!("invalid semver version '{}': {}", &cask_info.version, e))?;
 
-    let remote_formula = formula::fetch(cask, &package_formula.package.name, true, is_verbose)?;
+    // fetch from the address that the package installed from
+    let package_address = package_formula.get_source_address();
 
-    let remote_versions = remote_formula.get_versions()?;
+    // keep fetching the formula from the pinned ref
+    let formula_ref = package_formula.get_formula_ref();
+
+    let remote_formula =
+        formula::fetch(cask, &package_address, formula_ref, true, is_verbose).await?;
+
+    let remote_versions = remote_formula.get_versions(cask, is_refresh)?;
 
     let err_not_found_release = eyre::format_err!(
         "can not found any version on '{}' remote",
@@ -65,9 +74,12 @@ pub async fn update(
     } else {
         command_install::install(
             cask,
-            &package_formula.package.name,
+            &package_address,
             Some(latest_str),
+            formula_ref,
             is_verbose,
+            false,
+            script_permission,
         )
         .await?;
 
@@ -0,0 +1,435 @@
+#![deny(warnings)]
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+
+// The default seconds of remote tags will be cached
+const DEFAULT_TAGS_TTL: u64 = 600;
+
+// The default times to retry the download
+const DEFAULT_RETRIES: u32 = 3;
+
+// The default seconds to wait before the first retry
+const DEFAULT_RETRY_DELAY: u64 = 1;
+
+// The default max seconds to wait between retries
+const DEFAULT_MAX_RETRY_DELAY: u64 = 30;
+
+// The default seconds that a hook can run, if the formula does not declare it
+const DEFAULT_HOOK_TIMEOUT: u64 = 600;
+
+// The default max seconds that a hook can run, even the formula declare a longer one
+const DEFAULT_HOOK_MAX_TIMEOUT: u64 = 3600;
+
+// The default url template to fetch the formula file only
+const GITHUB_RAW_URL: &str = "https://raw.githubusercontent.com/{repository}/{ref}/{path}";
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Config {
+    pub cache: Option<CacheConfig>, // The cache settings
+    pub credentials: Option<HashMap<String, CredentialConfig>>, // The credentials of the host. eg. [credentials."github.com"]
+    pub formula: Option<FormulaConfig>,                         // The formula fetching settings
+    pub download: Option<DownloadConfig>,                       // The download settings
+    pub network: Option<NetworkConfig>, // The proxy and certificate settings, apply to git and download
+    pub security: Option<SecurityConfig>, // The security settings
+    pub hook: Option<HookConfig>,       // The settings of running the hooks of package
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct CacheConfig {
+    pub tags_ttl: Option<u64>, // How many seconds the remote tags will be cached. 0 means disable the cache
+    pub downloads_dir: Option<String>, // The folder of the download cache, it can be shared by machines. eg. /mnt/shared/cask
+    pub downloads_max_size: Option<u64>, // The max bytes of the download cache, the least recently used files will be removed
+    pub downloads_max_age: Option<u64>,  // The max seconds of the unused file in download cache
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct DownloadConfig {
+    pub retries: Option<u32>, // How many times to retry if download fail with the transient error. 0 means disable retry
+    pub retry_delay: Option<u64>, // How many seconds to wait before the first retry, it's doubled after each attempt
+    pub max_retry_delay: Option<u64>, // The max seconds to wait between retries
+    pub max_size: Option<u64>,    // The max bytes of the downloaded file. No limit if not provide
+    pub rewrite: Option<Vec<RewriteConfig>>, // The ordered rules to rewrite the download url. eg. [[download.rewrite]]
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RewriteConfig {
+    pub from: String, // The url prefix to be replaced. eg. https://github.com/
+    pub to: String,   // The new url prefix. eg. https://mirror.example.com/github/
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct NetworkConfig {
+    pub http_proxy: Option<String>, // The proxy for http request. eg. http://127.0.0.1:8080
+    pub https_proxy: Option<String>, // The proxy for https request
+    pub no_proxy: Option<String>, // The comma-separated hosts that do not use the proxy. eg. localhost,.example.com
+    pub ca_file: Option<String>,  // The PEM file of the extra trusted root certificates
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct SecurityConfig {
+    pub require_signature: Option<bool>, // Refuse to install the package which resource is not signed
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct HookConfig {
+    pub timeout: Option<u64>, // The seconds that a hook can run if the formula does not declare it
+    pub max_timeout: Option<u64>, // The max seconds that a hook can run, it caps the timeout of formula
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct FormulaConfig {
+    pub raw_url: Option<HashMap<String, String>>, // The url template to fetch the formula file of the host. eg. [formula.raw_url] "github.com" = "..."
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct CredentialConfig {
+    pub username: Option<String>, // The username of basic auth. Use bearer token if not provide
+    pub token: Option<String>,    // The token or password
+    pub token_env: Option<String>, // Read the token from the environment variable instead of writing it in the file
+}
+
+// get the first non-empty environment variable
+fn get_env(keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|k| env::var(k).ok())
+        .find(|v| !v.is_empty())
+}
+
+// expand the '~/' of path to the home dir
+fn expand_home(path: &str) -> Option<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(p) => dirs::home_dir().map(|h| h.join(p)),
+        None => Some(PathBuf::from(path)),
+    }
+}
+
+// whether the host matches the no_proxy list. eg. localhost,.example.com
+pub fn is_no_proxy(host: &str, no_proxy: &str) -> bool {
+    let host = host.to_lowercase();
+
+    no_proxy
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+
+            let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+}
+
+// load config from file, use the default config if file does not exist
+pub fn load(config_file: &Path) -> Result<Config, Report> {
+    if !config_file.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = fs::read_to_string(config_file)?;
+
+    toml::from_str(&content)
+        .map_err(|e| eyre::format_err!("invalid config file '{}': {}", config_file.display(), e))
+}
+
+impl Config {
+    pub fn tags_ttl(&self) -> u64 {
+        self.cache
+            .as_ref()
+            .and_then(|c| c.tags_ttl)
+            .unwrap_or(DEFAULT_TAGS_TTL)
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.download
+            .as_ref()
+            .and_then(|d| d.retries)
+            .unwrap_or(DEFAULT_RETRIES)
+    }
+
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_secs(
+            self.download
+                .as_ref()
+                .and_then(|d| d.retry_delay)
+                .unwrap_or(DEFAULT_RETRY_DELAY),
+        )
+    }
+
+    pub fn max_retry_delay(&self) -> Duration {
+        Duration::from_secs(
+            self.download
+                .as_ref()
+                .and_then(|d| d.max_retry_delay)
+                .unwrap_or(DEFAULT_MAX_RETRY_DELAY),
+        )
+    }
+
+    pub fn max_download_size(&self) -> Option<u64> {
+        self.download.as_ref().and_then(|d| d.max_size)
+    }
+
+    // the proxy for http request, fallback to the environment variable
+    pub fn http_proxy(&self) -> Option<String> {
+        self.network
+            .as_ref()
+            .and_then(|n| n.http_proxy.clone())
+            .or_else(|| get_env(&["http_proxy", "HTTP_PROXY"]))
+    }
+
+    // the proxy for https request, fallback to the environment variable
+    pub fn https_proxy(&self) -> Option<String> {
+        self.network
+            .as_ref()
+            .and_then(|n| n.https_proxy.clone())
+            .or_else(|| get_env(&["https_proxy", "HTTPS_PROXY"]))
+    }
+
+    pub fn no_proxy(&self) -> Option<String> {
+        self.network
+            .as_ref()
+            .and_then(|n| n.no_proxy.clone())
+            .or_else(|| get_env(&["no_proxy", "NO_PROXY"]))
+    }
+
+    pub fn ca_file(&self) -> Option<PathBuf> {
+        let file = self.network.as_ref().and_then(|n| n.ca_file.as_ref())?;
+
+        expand_home(file)
+    }
+
+    pub fn downloads_dir(&self) -> Option<PathBuf> {
+        let dir = self.cache.as_ref().and_then(|c| c.downloads_dir.as_ref())?;
+
+        expand_home(dir)
+    }
+
+    pub fn downloads_max_size(&self) -> Option<u64> {
+        self.cache.as_ref().and_then(|c| c.downloads_max_size)
+    }
+
+    pub fn downloads_max_age(&self) -> Option<Duration> {
+        self.cache
+            .as_ref()
+            .and_then(|c| c.downloads_max_age)
+            .map(Duration::from_secs)
+    }
+
+    // get the timeout of hook, the timeout declared by formula can not exceed the max timeout
+    pub fn hook_timeout(&self, declared: Option<u64>) -> Duration {
+        let timeout = declared
+            .or_else(|| self.hook.as_ref().and_then(|h| h.timeout))
+            .unwrap_or(DEFAULT_HOOK_TIMEOUT);
+
+        let max_timeout = self
+            .hook
+            .as_ref()
+            .and_then(|h| h.max_timeout)
+            .unwrap_or(DEFAULT_HOOK_MAX_TIMEOUT);
+
+        Duration::from_secs(timeout.min(max_timeout))
+    }
+
+    // get the proxy of url, returns None if the url does not use proxy
+    pub fn proxy_for(&self, url: &str) -> Option<String> {
+        let u = url::Url::parse(url).ok()?;
+        let host = u.host_str()?;
+
+        let proxy = match u.scheme() {
+            "http" => self.http_proxy(),
+            "https" => self.https_proxy(),
+            _ => None,
+        }?;
+
+        match self.no_proxy() {
+            Some(no_proxy) if is_no_proxy(host, &no_proxy) => None,
+            _ => Some(proxy),
+        }
+    }
+
+    // whether the host of url is listed in no_proxy, the proxy should be bypassed explicitly
+    pub fn is_no_proxy_url(&self, url: &str) -> bool {
+        let host = match url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+        {
+            Some(h) => h,
+            None => return false,
+        };
+
+        self.no_proxy()
+            .map(|no_proxy| is_no_proxy(&host, &no_proxy))
+            .unwrap_or(false)
+    }
+
+    // rewrite the download url with the first matched rule
+    pub fn rewrite_url(&self, url: &str) -> String {
+        let rules = self
+            .download
+            .as_ref()
+            .and_then(|d| d.rewrite.as_deref())
+            .unwrap_or_default();
+
+        for rule in rules {
+            if rule.from.is_empty() {
+                continue;
+            }
+
+            if let Some(rest) = url.strip_prefix(&rule.from) {
+                return format!("{}{}", rule.to, rest);
+            }
+        }
+
+        url.to_string()
+    }
+
+    // get the raw url template of the host, empty template means disabled
+    pub fn raw_url_template(&self, host: &str) -> Option<String> {
+        let template = self
+            .formula
+            .as_ref()
+            .and_then(|f| f.raw_url.as_ref())
+            .and_then(|m| m.get(host).cloned())
+            .or_else(|| {
+                if host == "github.com" {
+                    Some(GITHUB_RAW_URL.to_string())
+                } else {
+                    None
+                }
+            })?;
+
+        if template.is_empty() {
+            None
+        } else {
+            Some(template)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, time::Duration};
+
+    use crate::config;
+
+    #[test]
+    fn test_load_config() {
+        let config_path = env::current_dir()
+            .unwrap()
+            .join("fixtures")
+            .join("config")
+            .join("config.toml");
+
+        let c = config::load(&config_path).unwrap();
+
+        assert_eq!(c.tags_ttl(), 3600);
+        assert_eq!(c.retries(), 5);
+        assert_eq!(c.retry_delay(), Duration::from_secs(2));
+        assert_eq!(c.max_retry_delay(), Duration::from_secs(30));
+
+        let credentials = c.credentials.as_ref().unwrap();
+        let github = credentials.get("github.com").unwrap();
+
+        assert_eq!(github.username.as_deref(), Some("x-access-token"));
+        assert_eq!(github.token_env.as_deref(), Some("GITHUB_TOKEN"));
+
+        assert_eq!(c.raw_url_template("github.com"), None);
+        assert_eq!(
+            c.raw_url_template("gitlab.com").as_deref(),
+            Some("https://gitlab.com/{repository}/-/raw/{ref}/{path}")
+        );
+        assert_eq!(c.raw_url_template("example.com"), None);
+
+        assert_eq!(
+            c.rewrite_url("https://github.com/axetroy/dvm/releases/download/v1.0.0/dvm.tar.gz"),
+            "https://mirror.example.com/github/axetroy/dvm/releases/download/v1.0.0/dvm.tar.gz"
+        );
+        assert_eq!(
+            c.rewrite_url(
+                "https://github.com/cask-pkg/cask.rs/releases/download/v1.0.0/cask.tar.gz"
+            ),
+            "https://cask.example.com/cask.rs/releases/download/v1.0.0/cask.tar.gz"
+        );
+        assert_eq!(
+            c.rewrite_url("https://example.com/dvm.tar.gz"),
+            "https://example.com/dvm.tar.gz"
+        );
+
+        assert_eq!(c.hook_timeout(None), Duration::from_secs(300));
+        assert_eq!(c.hook_timeout(Some(60)), Duration::from_secs(60));
+        assert_eq!(c.hook_timeout(Some(7200)), Duration::from_secs(1800));
+    }
+
+    #[test]
+    fn test_proxy_for() {
+        let c = config::Config {
+            network: Some(config::NetworkConfig {
+                http_proxy: Some("http://127.0.0.1:8080".to_string()),
+                https_proxy: Some("http://127.0.0.1:8443".to_string()),
+                no_proxy: Some("localhost, .corp.example.com".to_string()),
+                ca_file: Some("~/ca.pem".to_string()),
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            c.proxy_for("http://github.com/axetroy/gpm.rs").as_deref(),
+            Some("http://127.0.0.1:8080")
+        );
+        assert_eq!(
+            c.proxy_for("https://github.com/axetroy/gpm.rs").as_deref(),
+            Some("http://127.0.0.1:8443")
+        );
+        assert_eq!(c.proxy_for("https://git.corp.example.com/tool"), None);
+        assert_eq!(c.proxy_for("https://localhost/tool"), None);
+        assert_eq!(c.proxy_for("ssh://git@github.com/axetroy/gpm.rs"), None);
+        assert_eq!(c.proxy_for("git@github.com:axetroy/gpm.rs.git"), None);
+
+        assert!(c.is_no_proxy_url("https://git.corp.example.com/tool"));
+        assert!(!c.is_no_proxy_url("https://github.com/axetroy/gpm.rs"));
+
+        assert_eq!(c.ca_file(), Some(dirs::home_dir().unwrap().join("ca.pem")));
+    }
+
+    #[test]
+    fn test_is_no_proxy() {
+        assert!(config::is_no_proxy("example.com", "example.com"));
+        assert!(config::is_no_proxy("git.example.com", ".example.com"));
+        assert!(config::is_no_proxy("git.example.com", "*.example.com"));
+        assert!(config::is_no_proxy(
+            "Git.Example.com",
+            "localhost,example.com"
+        ));
+        assert!(config::is_no_proxy("github.com", "*"));
+        assert!(!config::is_no_proxy("notexample.com", "example.com"));
+        assert!(!config::is_no_proxy("github.com", ""));
+    }
+
+    #[test]
+    fn test_load_config_if_not_exist() {
+        let config_path = env::current_dir()
+            .unwrap()
+            .join("fixtures")
+            .join("config")
+            .join("not_exist.toml");
+
+        let c = config::load(&config_path).unwrap();
+
+        assert_eq!(c.tags_ttl(), 600);
+        assert_eq!(c.retries(), 3);
+        assert_eq!(
+            c.raw_url_template("github.com").as_deref(),
+            Some("https://raw.githubusercontent.com/{repository}/{ref}/{path}")
+        );
+    }
+}
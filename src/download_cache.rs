@@ -0,0 +1,339 @@
+#![deny(warnings)]
+
+use crate::cask;
+
+use std::{
+    collections::HashMap,
+    fs,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Deserialize, Serialize, Debug)]
+struct UrlIndex {
+    url: String,    // The url of the downloaded file
+    sha256: String, // The SHA256 of the downloaded file
+}
+
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub sha256: String,          // The SHA256 of the file, it's the key of cache
+    pub urls: Vec<String>,       // The urls that the file downloaded from
+    pub size: u64,               // The bytes of the file
+    pub accessed_at: SystemTime, // The last time the file used
+}
+
+fn hash_of(s: &str) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(s);
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_of_file(file: &Path) -> Result<String, Report> {
+    let mut f = File::open(file)?;
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut f, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// the key of cache must be a SHA256, or it may point to the file outside of the cache
+fn is_sha256(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn blobs_dir(cask: &cask::Cask) -> Result<PathBuf, Report> {
+    Ok(cask.downloads_cache_dir()?.join("sha256"))
+}
+
+fn urls_dir(cask: &cask::Cask) -> Result<PathBuf, Report> {
+    Ok(cask.downloads_cache_dir()?.join("urls"))
+}
+
+fn get_url_index_file(cask: &cask::Cask, url: &str) -> Result<PathBuf, Report> {
+    Ok(urls_dir(cask)?.join(format!("{}.json", hash_of(url))))
+}
+
+fn read_url_index(file: &Path) -> Option<UrlIndex> {
+    let content = fs::read_to_string(file).ok()?;
+
+    serde_json::from_str::<UrlIndex>(&content).ok()
+}
+
+// mark the file as recently used, the least recently used files will be removed first
+fn touch(file: &Path) {
+    if let Ok(f) = File::options().write(true).open(file) {
+        f.set_modified(SystemTime::now()).ok();
+    }
+}
+
+// get the cached file of the url.
+// the checksum is used as the key if provided, so the file can be shared by different urls.
+// the file is hashed again before it's returned, a broken or modified file will never be used
+pub fn get(cask: &cask::Cask, url: &str, checksum: Option<&str>) -> Option<PathBuf> {
+    let sha256 = match checksum {
+        Some(c) => c.to_lowercase(),
+        None => {
+            let index = read_url_index(&get_url_index_file(cask, url).ok()?)?;
+
+            if index.url != url {
+                return None;
+            }
+
+            index.sha256.to_lowercase()
+        }
+    };
+
+    if !is_sha256(&sha256) {
+        return None;
+    }
+
+    let file = blobs_dir(cask).ok()?.join(&sha256);
+
+    if !file.is_file() || hash_of_file(&file).ok()? != sha256 {
+        return None;
+    }
+
+    touch(&file);
+
+    Some(file)
+}
+
+// put the downloaded file into cache
+pub fn put(cask: &cask::Cask, url: &str, file: &Path) -> Result<PathBuf, Report> {
+    let sha256 = hash_of_file(file)?;
+    let blobs_dir = blobs_dir(cask)?;
+    let urls_dir = urls_dir(cask)?;
+    let blob = blobs_dir.join(&sha256);
+
+    fs::create_dir_all(&blobs_dir)?;
+    fs::create_dir_all(&urls_dir)?;
+
+    if blob.exists() {
+        touch(&blob);
+    } else {
+        // the cache may be shared by other processes, make sure the file is complete before it can be seen
+        let temp = blobs_dir.join(format!("{}.{}.tmp", sha256, process::id()));
+
+        fs::copy(file, &temp)?;
+        fs::rename(&temp, &blob)?;
+    }
+
+    fs::write(
+        get_url_index_file(cask, url)?,
+        serde_json::to_string(&UrlIndex {
+            url: url.to_string(),
+            sha256,
+        })?,
+    )?;
+
+    Ok(blob)
+}
+
+// list the cached files, the most recently used at the head
+pub fn list(cask: &cask::Cask) -> Result<Vec<CacheEntry>, Report> {
+    let blobs_dir = blobs_dir(cask)?;
+    let urls_dir = urls_dir(cask)?;
+
+    if !blobs_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut urls: HashMap<String, Vec<String>> = HashMap::new();
+
+    if urls_dir.exists() {
+        for entry in fs::read_dir(&urls_dir)? {
+            if let Some(index) = read_url_index(&entry?.path()) {
+                urls.entry(index.sha256).or_default().push(index.url);
+            }
+        }
+    }
+
+    let mut entries: Vec<CacheEntry> = vec![];
+
+    for entry in fs::read_dir(&blobs_dir)? {
+        let entry = entry?;
+        let sha256 = entry.file_name().to_string_lossy().to_string();
+
+        // the file is writing by other process
+        if sha256.ends_with(".tmp") {
+            continue;
+        }
+
+        let meta = entry.metadata()?;
+
+        entries.push(CacheEntry {
+            urls: urls.remove(&sha256).unwrap_or_default(),
+            sha256,
+            size: meta.len(),
+            accessed_at: meta.modified()?,
+        })
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.accessed_at));
+
+    Ok(entries)
+}
+
+fn remove(cask: &cask::Cask, entry: &CacheEntry) -> Result<(), Report> {
+    for url in &entry.urls {
+        let index_file = get_url_index_file(cask, url)?;
+
+        if index_file.exists() {
+            fs::remove_file(index_file)?;
+        }
+    }
+
+    let blob = blobs_dir(cask)?.join(&entry.sha256);
+
+    if blob.exists() {
+        fs::remove_file(blob)?;
+    }
+
+    Ok(())
+}
+
+// remove the files that unused longer than max age,
+// then remove the least recently used files until the total size less than max size.
+// returns the removed entries
+pub fn prune(
+    cask: &cask::Cask,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> Result<Vec<CacheEntry>, Report> {
+    let now = SystemTime::now();
+    let mut total_size: u64 = 0;
+    let mut removed: Vec<CacheEntry> = vec![];
+
+    for entry in list(cask)? {
+        let is_expired = match max_age {
+            Some(age) => now.duration_since(entry.accessed_at).unwrap_or_default() > age,
+            None => false,
+        };
+
+        let is_oversize = match max_size {
+            Some(size) => total_size + entry.size > size,
+            None => false,
+        };
+
+        if is_expired || is_oversize {
+            remove(cask, &entry)?;
+            removed.push(entry);
+        } else {
+            total_size += entry.size;
+        }
+    }
+
+    Ok(removed)
+}
+
+// remove all the cached files
+pub fn clear(cask: &cask::Cask) -> Result<(), Report> {
+    for dir in [blobs_dir(cask)?, urls_dir(cask)?] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, thread, time::Duration};
+
+    use crate::{cask, download_cache};
+
+    #[test]
+    fn test_download_cache() {
+        let root_dir = env::temp_dir().join("cask_test_download_cache");
+
+        fs::remove_dir_all(&root_dir).ok();
+        fs::create_dir_all(&root_dir).unwrap();
+
+        let c = cask::new(&root_dir);
+
+        let file = root_dir.join("file.tar.gz");
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        fs::write(&file, "hello world").unwrap();
+
+        assert!(download_cache::get(&c, "https://example.com/a.tar.gz", None).is_none());
+
+        download_cache::put(&c, "https://example.com/a.tar.gz", &file).unwrap();
+
+        let cached = download_cache::get(&c, "https://example.com/a.tar.gz", None).unwrap();
+
+        assert_eq!(fs::read_to_string(&cached).unwrap(), "hello world");
+
+        // the same file of different url
+        let cached = download_cache::get(
+            &c,
+            "https://mirror.example.com/a.tar.gz",
+            Some(&sha256.to_uppercase()),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&cached).unwrap(), "hello world");
+
+        thread::sleep(Duration::from_millis(20));
+
+        fs::write(&file, "hello cask").unwrap();
+
+        download_cache::put(&c, "https://example.com/b.tar.gz", &file).unwrap();
+
+        let entries = download_cache::list(&c).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].urls, vec!["https://example.com/b.tar.gz"]);
+        assert_eq!(entries[1].sha256, sha256);
+
+        // the least recently used file is removed
+        let removed = download_cache::prune(&c, Some(10), None).unwrap();
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].sha256, sha256);
+        assert!(download_cache::get(&c, "https://example.com/a.tar.gz", None).is_none());
+        assert!(download_cache::get(&c, "https://example.com/b.tar.gz", None).is_some());
+
+        // the broken file is not used
+        let cached = download_cache::get(&c, "https://example.com/b.tar.gz", None).unwrap();
+
+        fs::write(&cached, "hello world").unwrap();
+
+        assert!(download_cache::get(&c, "https://example.com/b.tar.gz", None).is_none());
+
+        // the index can not point to the file outside of the cache
+        let index_file =
+            download_cache::get_url_index_file(&c, "https://example.com/c.tar.gz").unwrap();
+
+        fs::write(
+            &index_file,
+            r#"{"url":"https://example.com/c.tar.gz","sha256":"../../../file.tar.gz"}"#,
+        )
+        .unwrap();
+
+        assert!(download_cache::get(&c, "https://example.com/c.tar.gz", None).is_none());
+        assert!(download_cache::get(
+            &c,
+            "https://example.com/c.tar.gz",
+            Some("../../file.tar.gz")
+        )
+        .is_none());
+
+        download_cache::clear(&c).unwrap();
+
+        assert!(download_cache::list(&c).unwrap().is_empty());
+
+        fs::remove_dir_all(&root_dir).ok();
+    }
+}
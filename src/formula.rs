@@ -1,6 +1,6 @@
 #![deny(warnings)]
 
-use crate::{cask, hooker};
+use crate::{address, cask, config, hooker, policy, tag_cache};
 use std::collections::HashMap;
 
 use std::{
@@ -8,10 +8,11 @@ use std::{
     fs::File,
     io::{ErrorKind, Read},
     path::{Path, PathBuf},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use eyre::Report;
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use tinytemplate::TinyTemplate;
 use url::Url;
@@ -24,6 +25,12 @@ pub struct Formula {
     pub repository: String, // The repository of this formula
     #[serde(skip)]
     pub filepath: PathBuf, // The filepath of this formula
+    #[serde(skip)]
+    pub subpath: Option<String>, // The subdirectory of the repository that formula locate in
+    #[serde(skip)]
+    pub formula_ref: Option<String>, // The git ref that formula fetched from
+    #[serde(skip)]
+    pub formula_commit: Option<String>, // The commit hash of formula repository
 
     pub cask: Option<Cask>, // The cask information that generated by cask. This field is only available after the package is installed.
     pub package: Package,   // The package information
@@ -43,6 +50,24 @@ pub struct Cask {
     pub created_at: String, // The package installed date
     pub version: String,    // The version is using for package
     pub repository: String, // The package installed from the repository url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subpath: Option<String>, // The subdirectory of the repository that formula locate in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formula_ref: Option<String>, // The pinned git ref of formula. eg. branch, tag or commit hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formula_commit: Option<String>, // The commit hash of formula when the package installed
+}
+
+impl Cask {
+    // the [cask] section of the installed Cask.toml
+    pub fn to_toml(&self) -> Result<String, Report> {
+        #[derive(Serialize)]
+        struct Section<'a> {
+            cask: &'a Cask,
+        }
+
+        Ok(toml::to_string(&Section { cask: self })?)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -67,6 +92,7 @@ pub struct Package {
     pub keywords: Option<Vec<String>>, // The keywords of the package
     pub license: Option<String>,       // The license of the package
     pub homepage: Option<String>,      // The homepage of the package
+    pub public_key: Option<String>, // The minisign or ed25519 public key to verify the signature of resource
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -90,10 +116,42 @@ pub enum ResourceTarget {
     Simple(String),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ResourceChecksum {
+    Single(String),                    // The checksum of the resource
+    Versions(HashMap<String, String>), // The checksum of each version. eg. { "1.0.0" = "..." }
+}
+
+impl ResourceChecksum {
+    // get the checksum of version, the version may be with 'v' prefix
+    pub fn get(&self, version: &str) -> Option<&str> {
+        match self {
+            ResourceChecksum::Single(checksum) => Some(checksum),
+            ResourceChecksum::Versions(checksums) => checksums
+                .get(version)
+                .or_else(|| {
+                    let v = Version::parse(version.trim_start_matches('v')).ok()?;
+
+                    checksums
+                        .iter()
+                        .find(|(key, _)| {
+                            Version::parse(key.trim_start_matches('v')).ok().as_ref() == Some(&v)
+                        })
+                        .map(|(_, checksum)| checksum)
+                })
+                .map(|checksum| checksum.as_str()),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ResourceTargetDetail {
-    pub url: String,              // The url will be download when install the package
-    pub checksum: Option<String>, // The hash256 of download resource
+    pub url: String,                  // The url will be download when install the package
+    pub mirrors: Option<Vec<String>>, // The fallback urls of the same resource, tried in order if the url fails
+    pub checksum: Option<ResourceChecksum>, // The checksum of download resource. eg. <sha256 hex>, sha512:<hex>, blake3:<hex>, sha256-<base64>
+    pub checksum_url: Option<String>,       // The url of published checksum file. eg. SHA256SUMS
+    pub signature_url: Option<String>, // The url of detached signature. eg. minisign .minisig file
     pub extension: Option<extractor::Extension>, // The extension name of download resource. optional value: ".tar.gz" ".tar" ".zip"
     pub path: Option<String>, // The folder that binary file locate in the tarball
 }
@@ -101,12 +159,16 @@ pub struct ResourceTargetDetail {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ResourceTargetExecutable {
     pub executable: String, // The url will be download when install the package
-    pub checksum: Option<String>, // The hash256 of download resource
+    pub mirrors: Option<Vec<String>>, // The fallback urls of the same resource, tried in order if the url fails
+    pub checksum: Option<ResourceChecksum>, // The checksum of download resource. eg. <sha256 hex>, sha512:<hex>, blake3:<hex>, sha256-<base64>
+    pub checksum_url: Option<String>,       // The url of published checksum file. eg. SHA256SUMS
+    pub signature_url: Option<String>, // The url of detached signature. eg. minisign .minisig file
 }
 
 #[derive(Serialize)]
 pub struct URLTemplateContext<'a> {
     version: &'a str,
+    previous_version: Option<&'a str>, // The installed version before update
     package: &'a Package,
     context: Option<&'a HashMap<String, String>>,
 }
@@ -144,13 +206,48 @@ pub fn new(formula_file: &Path, repo: &str) -> Result<Formula, Report> {
 }
 
 pub struct DownloadTarget {
-    pub url: String,
+    pub url: String,          // The url to download, it may be rewritten to the mirror
+    pub original_url: String, // The url rendered from formula, used to identify the resource
+    pub mirrors: Vec<String>, // The fallback urls, it may be rewritten too
+    pub checksum_url: Option<String>, // The url of checksum file, the checksum is looked up by the file name of url
+    pub is_checksum_missing: bool, // The checksums of other versions are recorded, but not this version
+    pub signature_url: Option<String>, // The url of detached signature of resource
     pub path: String,
-    pub checksum: Option<String>,
+    pub checksum: Option<downloader::Checksum>,
     pub ext: String,
     pub executable: bool, // if target is a executable file not a tarball
 }
 
+impl DownloadTarget {
+    // rewrite the download url with the rules of config
+    pub fn rewrite(mut self, config: &config::Config) -> DownloadTarget {
+        self.url = config.rewrite_url(&self.original_url);
+        self.mirrors = self.mirrors.iter().map(|m| config.rewrite_url(m)).collect();
+        self.checksum_url = self.checksum_url.as_ref().map(|u| config.rewrite_url(u));
+        self.signature_url = self.signature_url.as_ref().map(|u| config.rewrite_url(u));
+        self
+    }
+
+    // the file name of resource. eg. dvm_linux_amd64.tar.gz
+    pub fn filename(&self) -> Option<String> {
+        let u = Url::parse(&self.original_url).ok()?;
+
+        u.path_segments()?
+            .next_back()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    }
+
+    // the urls to download in order
+    pub fn urls(&self) -> Vec<&str> {
+        let mut urls = vec![self.url.as_str()];
+
+        urls.extend(self.mirrors.iter().map(|m| m.as_str()));
+
+        urls
+    }
+}
+
 fn get_formula_git_url(package_name: &str) -> String {
     format!("https://{}.git", package_name)
 }
@@ -168,49 +265,105 @@ fn print_publishing_msg() {
     eprintln!("{}", msg);
 }
 
-pub fn fetch(
+pub async fn fetch(
     cask: &cask::Cask,
     package_name: &str,
+    formula_ref: Option<&str>,
+    temp: bool,
+    is_verbose: bool,
+) -> Result<Formula, Report> {
+    let policy = cask.policy()?;
+
+    let f = fetch_formula(cask, &policy, package_name, formula_ref, temp, is_verbose).await?;
+
+    // the package name declared in formula may be different from the address
+    policy.check_package(&f.package.name)?;
+
+    Ok(f)
+}
+
+async fn fetch_formula(
+    cask: &cask::Cask,
+    policy: &policy::Policies,
+    package_name: &str,
+    formula_ref: Option<&str>,
     temp: bool,
     is_verbose: bool,
 ) -> Result<Formula, Report> {
     eprintln!("Fetching {} formula...", package_name);
 
-    // if package is a url
-    if let Ok(package_addr) = Url::parse(package_name) {
-        let scheme = package_addr.scheme();
-        return match scheme {
-            "http" | "https" => {
-                let repo = git::new(package_addr.as_str())?;
-                let is_package_repo_exist = repo.is_exist()?;
+    // the formula may locate in the subdirectory of repository
+    let (package_address, subpath) = address::split_subpath(package_name)?;
+    let subpath = subpath.as_deref();
+
+    // if package is a local formula file or repository
+    if let Some(local_path) = address::get_local_path(package_address) {
+        policy.check_local(package_address)?;
+
+        return fetch_with_local_path(
+            cask,
+            package_name,
+            &local_path,
+            subpath,
+            formula_ref,
+            temp,
+            is_verbose,
+        )
+        .await;
+    }
 
-                if is_package_repo_exist {
-                    fetch_with_git_url(cask, package_name, package_addr.as_str(), temp, is_verbose)
-                } else {
-                    Err(eyre::format_err!(
-                        "The package '{}' does not exist!",
-                        package_name
-                    ))
-                }
-            }
-            _ => Err(eyre::format_err!(
-                "Not support the protocol '{}' of package address.",
-                scheme
-            )),
+    // if package is a url or ssh address
+    if let Some(package_addr) = address::parse(package_address)? {
+        policy.check_source(&package_addr.name)?;
+
+        let repo = cask.git(&package_addr.url)?;
+        let is_package_repo_exist = repo.is_exist()?;
+
+        return if is_package_repo_exist {
+            fetch_with_git_url(
+                cask,
+                &address::join_subpath(&package_addr.name, subpath),
+                &package_addr.url,
+                subpath,
+                formula_ref,
+                temp,
+                is_verbose,
+            )
+            .await
+        } else {
+            Err(eyre::format_err!(
+                "The package '{}' does not exist!",
+                package_name
+            ))
         };
     }
 
-    let fo = find_package_in_build_in(cask, package_name)?;
+    policy.check_source(package_address)?;
+
+    let fo = if subpath.is_none() && formula_ref.is_none() {
+        find_package_in_build_in(cask, package_address)?
+    } else {
+        None
+    };
 
     if let Some(f) = fo {
         Ok(f)
     } else {
-        let package_repo_url = get_formula_git_url(package_name);
+        let package_repo_url = get_formula_git_url(package_address);
 
-        let is_repo_exist = git::new(&package_repo_url)?.is_exist()?;
+        let is_repo_exist = cask.git(&package_repo_url)?.is_exist()?;
 
         if is_repo_exist {
-            fetch_with_git_url(cask, package_name, &package_repo_url, temp, is_verbose)
+            fetch_with_git_url(
+                cask,
+                &address::join_subpath(package_address, subpath),
+                &package_repo_url,
+                subpath,
+                formula_ref,
+                temp,
+                is_verbose,
+            )
+            .await
         } else {
             Err(eyre::format_err!("can not found package {}", package_name))
         }
@@ -241,11 +394,70 @@ fn find_package_in_build_in(
     Ok(None)
 }
 
+// fetch formula from local file, directory or repository
+async fn fetch_with_local_path(
+    cask: &cask::Cask,
+    package_name: &str,
+    local_path: &Path,
+    subpath: Option<&str>,
+    formula_ref: Option<&str>,
+    temp: bool,
+    is_verbose: bool,
+) -> Result<Formula, Report> {
+    let abs_path = env::current_dir()?
+        .join(local_path)
+        .canonicalize()
+        .map_err(|_| eyre::format_err!("can not found package {}", package_name))?;
+
+    let abs_path_str = format!("{}", abs_path.display());
+
+    if abs_path.is_file() {
+        if subpath.is_some() || formula_ref.is_some() {
+            return Err(eyre::format_err!(
+                "the formula file '{}' can not have a subpath or git ref",
+                abs_path.display()
+            ));
+        }
+
+        // the formula file
+        return new(&abs_path, &abs_path_str);
+    }
+
+    let cask_file_path = abs_path.join(subpath.unwrap_or("")).join("Cask.toml");
+
+    // the working directory can not checkout the ref, clone it instead
+    if cask_file_path.is_file() && formula_ref.is_none() {
+        // the working directory of repository
+        let mut f = new(&cask_file_path, &abs_path_str)?;
+
+        f.subpath = subpath.map(|s| s.to_string());
+
+        return Ok(f);
+    }
+
+    // try clone from the bare repository
+    let git_url = Url::from_file_path(&abs_path)
+        .map_err(|_| eyre::format_err!("invalid local path '{}'", abs_path.display()))?;
+
+    fetch_with_git_url(
+        cask,
+        &address::join_subpath(&abs_path_str, subpath),
+        git_url.as_str(),
+        subpath,
+        formula_ref,
+        temp,
+        is_verbose,
+    )
+    .await
+}
+
 // fetch remote formula
-fn fetch_with_git_url(
+async fn fetch_with_git_url(
     cask: &cask::Cask,
     package_name: &str,
     git_url: &str,
+    subpath: Option<&str>,
+    formula_ref: Option<&str>,
     temp: bool,
     is_verbose: bool,
 ) -> Result<Formula, Report> {
@@ -257,11 +469,15 @@ fn fetch_with_git_url(
         t.as_secs()
     };
 
+    // the repository is moved into the package folder once the package name is known,
+    // it may be different from the address
     let formula_cloned_dir = {
         if temp {
             env::temp_dir().join(format!("cask_formula_{}", unix_time))
         } else {
-            cask.package_dir(package_name).join("repository")
+            cask.cache_dir()
+                .join("formula")
+                .join(format!("{}_{}", unix_time, std::process::id()))
         }
     };
 
@@ -269,22 +485,35 @@ fn fetch_with_git_url(
         fs::remove_dir_all(&formula_cloned_dir)?;
     }
 
-    let cask_file_path = formula_cloned_dir.join("Cask.toml");
+    let cask_file_path = formula_cloned_dir
+        .join(subpath.unwrap_or(""))
+        .join("Cask.toml");
+
+    let start = Instant::now();
+
+    // try fetching the formula file only, it's much faster than clone the repository.
+    // the hooks run in the repository and may rely on its files, so clone it if the formula has hooks
+    let formula_file = fetch_formula_file(cask, git_url, subpath, formula_ref, is_verbose)
+        .await
+        .filter(|f| temp || !has_hook(&f.content));
+
+    let formula_commit = match formula_file {
+        Some(file) => {
+            if let Some(parent) = cask_file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::write(&cask_file_path, file.content)?;
+
+            file.commit
+        }
+        None => {
+            let r = clone_formula(cask, git_url, &formula_cloned_dir, formula_ref, is_verbose);
+
+            if r.is_err() || !cask_file_path.exists() {
+                fs::remove_dir_all(&formula_cloned_dir).ok();
+                r?;
 
-    match git::new(git_url)?.clone(
-        &formula_cloned_dir,
-        git::CloneOption {
-            depth: Some(1),
-            quiet: Some(!is_verbose),
-            verbose: Some(is_verbose),
-            progress: Some(!is_verbose),
-            single_branch: Some(true),
-            dissociate: Some(true),
-            filter: Some("tree:0".to_string()),
-        },
-    ) {
-        Ok(()) => {
-            if !cask_file_path.exists() {
                 print_publishing_msg();
 
                 return Err(eyre::format_err!(
@@ -293,23 +522,236 @@ fn fetch_with_git_url(
                 ));
             }
 
-            match new(&cask_file_path, git_url) {
-                Ok(r) => {
-                    if temp {
-                        fs::remove_dir_all(formula_cloned_dir)?;
-                    }
-                    Ok(r)
+            git::head(&formula_cloned_dir).ok()
+        }
+    };
+
+    if is_verbose {
+        eprintln!(
+            "Fetched the formula of {} in {:.2?}",
+            package_name,
+            start.elapsed()
+        );
+    }
+
+    let result = new(&cask_file_path, git_url).and_then(|mut r| {
+        r.subpath = subpath.map(|s| s.to_string());
+        r.formula_ref = formula_ref.map(|s| s.to_string());
+        r.formula_commit = formula_commit;
+
+        if !temp {
+            let repository_dir = cask.package_repository_dir(&r.package.name);
+
+            if repository_dir.exists() {
+                fs::remove_dir_all(&repository_dir)?;
+            }
+
+            if let Some(parent) = repository_dir.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::rename(&formula_cloned_dir, &repository_dir)?;
+
+            r.filepath = repository_dir.join(subpath.unwrap_or("")).join("Cask.toml");
+        }
+
+        Ok(r)
+    });
+
+    // the repository has been moved if succeed
+    if formula_cloned_dir.exists() {
+        fs::remove_dir_all(&formula_cloned_dir)?;
+    }
+
+    result
+}
+
+// render the raw url of formula file with the template of host
+fn get_formula_raw_url(
+    cask: &cask::Cask,
+    git_url: &str,
+    reference: &str,
+    path: &str,
+) -> Option<String> {
+    #[derive(Serialize)]
+    struct RawURLContext<'a> {
+        repository: &'a str,
+        r#ref: &'a str,
+        path: &'a str,
+    }
+
+    let addr = address::parse(git_url).ok().flatten()?;
+    let (host, repository) = addr.name.split_once('/')?;
+    let template = cask.config().ok()?.raw_url_template(host)?;
+
+    let mut tt = TinyTemplate::new();
+
+    tt.set_default_formatter(&tinytemplate::format_unescaped);
+    tt.add_template("raw_url_template", &template).ok()?;
+
+    tt.render(
+        "raw_url_template",
+        &RawURLContext {
+            repository,
+            r#ref: reference,
+            path,
+        },
+    )
+    .ok()
+}
+
+// fetch the formula file only without clone.
+// returns None if the remote does not support, then the repository should be cloned
+async fn fetch_formula_file(
+    cask: &cask::Cask,
+    git_url: &str,
+    subpath: Option<&str>,
+    formula_ref: Option<&str>,
+    is_verbose: bool,
+) -> Option<git::ArchiveFile> {
+    let path = match subpath {
+        Some(p) => format!("{}/Cask.toml", p),
+        None => "Cask.toml".to_string(),
+    };
+    let reference = formula_ref.unwrap_or("HEAD");
+
+    // resolve the commit of ref first, so that the formula commit can be recorded for pinning
+    let commit = get_formula_raw_url(cask, git_url, reference, &path).and_then(|_| {
+        cask.git(git_url)
+            .ok()?
+            .resolve_ref(reference)
+            .ok()
+            .flatten()
+    });
+
+    if let Some((commit, raw_url)) = commit.and_then(|commit| {
+        get_formula_raw_url(cask, git_url, &commit, &path).map(|raw_url| (commit, raw_url))
+    }) {
+        let start = Instant::now();
+
+        let r = match cask.download_option(&raw_url) {
+            Ok(option) => downloader::fetch(&raw_url, &option).await,
+            Err(e) => Err(e),
+        };
+
+        match r {
+            Ok(content) => {
+                if is_verbose {
+                    eprintln!("Fetched {} in {:.2?}", raw_url, start.elapsed());
                 }
-                Err(e) => {
-                    if temp {
-                        fs::remove_dir_all(formula_cloned_dir)?;
-                    }
-                    Err(e)
+
+                return Some(git::ArchiveFile {
+                    commit: Some(commit),
+                    content,
+                });
+            }
+            Err(e) => {
+                if is_verbose {
+                    eprintln!("Fetch {} fail in {:.2?}: {}", raw_url, start.elapsed(), e);
                 }
             }
         }
-        Err(e) => Err(eyre::format_err!("{}", e)),
     }
+
+    // 'git upload-archive' is not available over http
+    if !git_url.starts_with("http://") && !git_url.starts_with("https://") {
+        let start = Instant::now();
+
+        let r = || -> Result<git::ArchiveFile, Report> {
+            Ok(cask.git(git_url)?.archive_file(reference, &path)?)
+        };
+
+        match r() {
+            Ok(file) => {
+                if is_verbose {
+                    eprintln!(
+                        "Fetched {} with git archive in {:.2?}",
+                        path,
+                        start.elapsed()
+                    );
+                }
+
+                return Some(file);
+            }
+            Err(e) => {
+                if is_verbose {
+                    eprintln!(
+                        "Fetch {} with git archive fail in {:.2?}: {}",
+                        path,
+                        start.elapsed(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// whether the formula file declares the hooks
+fn has_hook(content: &[u8]) -> bool {
+    std::str::from_utf8(content)
+        .ok()
+        .and_then(|c| toml::from_str::<toml::Value>(c).ok())
+        .map(|v| v.get("hook").is_some())
+        .unwrap_or(true)
+}
+
+// clone the formula repository, checkout the git ref if provided
+fn clone_formula(
+    cask: &cask::Cask,
+    git_url: &str,
+    dest: &Path,
+    formula_ref: Option<&str>,
+    is_verbose: bool,
+) -> Result<(), Report> {
+    let repo = cask.git(git_url)?;
+
+    // shallow clone the branch or tag
+    let r = repo.clone(
+        dest,
+        git::CloneOption {
+            depth: Some(1),
+            quiet: Some(!is_verbose),
+            verbose: Some(is_verbose),
+            progress: Some(!is_verbose),
+            single_branch: Some(true),
+            dissociate: Some(true),
+            filter: Some("tree:0".to_string()),
+            branch: formula_ref.map(|s| s.to_string()),
+        },
+    );
+
+    let reference = match (r, formula_ref) {
+        (Ok(()), _) => return Ok(()),
+        (Err(e), None) => return Err(eyre::format_err!("{}", e)),
+        (Err(_), Some(reference)) => reference,
+    };
+
+    // the ref may be a commit hash, clone the whole history then checkout it
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+
+    repo.clone(
+        dest,
+        git::CloneOption {
+            depth: None,
+            quiet: Some(!is_verbose),
+            verbose: Some(is_verbose),
+            progress: Some(!is_verbose),
+            single_branch: None,
+            dissociate: Some(true),
+            filter: Some("blob:none".to_string()),
+            branch: None,
+        },
+    )?;
+
+    repo.checkout(dest, reference)
+        .map_err(|_| eyre::format_err!("can not found the git ref '{}' of formula", reference))?;
+
+    Ok(())
 }
 
 impl<'a> Formula {
@@ -352,6 +794,18 @@ impl<'a> Formula {
         }
     }
 
+    // the context for the hooks of update
+    pub fn ger_update_renderer_context(
+        &'a self,
+        version: &'a str,
+        previous_version: &'a str,
+    ) -> URLTemplateContext<'a> {
+        URLTemplateContext {
+            previous_version: Some(previous_version),
+            ..self.ger_renderer_context(version)
+        }
+    }
+
     pub fn get_file_content(&self) -> String {
         self.file_content.clone()
     }
@@ -359,6 +813,7 @@ impl<'a> Formula {
     pub fn ger_renderer_context(&'a self, version: &'a str) -> URLTemplateContext<'a> {
         let render_context = URLTemplateContext {
             version,
+            previous_version: None,
             package: &self.package,
             context: self.context.as_ref(),
         };
@@ -387,8 +842,8 @@ impl<'a> Formula {
 
                 let default_ext = extractor::Extension::TarGz;
 
-                if let Some(sep) = u.path_segments() {
-                    let filename = sep.last().unwrap_or(default_ext.as_str());
+                if let Some(mut sep) = u.path_segments() {
+                    let filename = sep.next_back().unwrap_or(default_ext.as_str());
 
                     if filename.ends_with(extractor::Extension::TarGz.as_str()) {
                         Ok(extractor::Extension::TarGz.as_str())
@@ -439,14 +894,58 @@ impl<'a> Formula {
                 ResourceTarget::Simple(_) => get_ext_name_from_url()?.to_string(),
             };
 
-            let checksum = match resource_target {
-                ResourceTarget::Detailed(arch) => arch.checksum.clone(),
-                ResourceTarget::Executable(arch) => arch.checksum.clone(),
+            let render_url = |u: &String| -> Result<String, Report> {
+                let mut tt = TinyTemplate::new();
+
+                tt.add_template("url_template", u)?;
+
+                Ok(tt.render("url_template", &render_context)?)
+            };
+
+            let mirrors = match resource_target {
+                ResourceTarget::Detailed(arch) => arch.mirrors.clone(),
+                ResourceTarget::Executable(arch) => arch.mirrors.clone(),
+                ResourceTarget::Simple(_) => None,
+            }
+            .unwrap_or_default()
+            .iter()
+            .map(render_url)
+            .collect::<Result<Vec<String>, Report>>()?;
+
+            let resource_checksum = match resource_target {
+                ResourceTarget::Detailed(arch) => arch.checksum.as_ref(),
+                ResourceTarget::Executable(arch) => arch.checksum.as_ref(),
                 ResourceTarget::Simple(_) => None,
             };
 
+            let checksum = resource_checksum
+                .and_then(|c| c.get(version))
+                .map(|c| c.parse::<downloader::Checksum>())
+                .transpose()?;
+
+            let checksum_url = match resource_target {
+                ResourceTarget::Detailed(arch) => arch.checksum_url.as_ref(),
+                ResourceTarget::Executable(arch) => arch.checksum_url.as_ref(),
+                ResourceTarget::Simple(_) => None,
+            }
+            .map(render_url)
+            .transpose()?;
+
+            let signature_url = match resource_target {
+                ResourceTarget::Detailed(arch) => arch.signature_url.as_ref(),
+                ResourceTarget::Executable(arch) => arch.signature_url.as_ref(),
+                ResourceTarget::Simple(_) => None,
+            }
+            .map(render_url)
+            .transpose()?;
+
             Ok(DownloadTarget {
-                url: renderer_url,
+                url: renderer_url.clone(),
+                original_url: renderer_url,
+                mirrors,
+                checksum_url,
+                is_checksum_missing: resource_checksum.is_some() && checksum.is_none(),
+                signature_url,
                 path: path.trim().to_string(),
                 checksum,
                 ext: ext_name,
@@ -460,20 +959,76 @@ impl<'a> Formula {
         }
     }
 
+    // get the address that the formula fetched from, includes the subpath
+    pub fn get_source_address(&self) -> String {
+        let (repository, subpath) = if self.repository.is_empty() {
+            self.cask
+                .as_ref()
+                .map(|c| (c.repository.as_str(), c.subpath.as_deref()))
+                .unwrap_or(("", None))
+        } else {
+            (self.repository.as_str(), self.subpath.as_deref())
+        };
+
+        if repository.is_empty() {
+            self.package.name.clone()
+        } else {
+            address::join_subpath(repository, subpath)
+        }
+    }
+
+    // get the remote for listing tags.
+    // use the ssh address if the formula fetched with ssh from the same repository
+    fn get_tags_remote(&self) -> String {
+        let source = self.get_source_address();
+        let source = address::split_subpath(&source)
+            .map(|(s, _)| s.to_string())
+            .unwrap_or(source);
+
+        if address::is_ssh(&source)
+            && address::get_package_name(&source)
+                == address::get_package_name(&self.package.repository)
+        {
+            source
+        } else {
+            self.package.repository.clone()
+        }
+    }
+
+    // get the pinned git ref of formula
+    pub fn get_formula_ref(&self) -> Option<&str> {
+        self.formula_ref
+            .as_deref()
+            .or_else(|| self.cask.as_ref().and_then(|c| c.formula_ref.as_deref()))
+    }
+
+    // the folder that the hooks run in, it's the folder of formula in the repository
+    pub fn hook_dir(&self, cask: &cask::Cask) -> PathBuf {
+        let subpath = self
+            .subpath
+            .as_deref()
+            .or_else(|| self.cask.as_ref().and_then(|c| c.subpath.as_deref()));
+
+        cask.package_repository_dir(&self.package.name)
+            .join(subpath.unwrap_or(""))
+    }
+
     // get all remote versions
-    pub fn get_versions(&self) -> Result<Vec<String>, Report> {
+    pub fn get_versions(&self, cask: &cask::Cask, is_refresh: bool) -> Result<Vec<String>, Report> {
         if let Some(versions) = &self.package.versions {
             Ok(versions.to_vec())
         } else {
-            git::new(&self.package.repository)?
-                .versions()
-                .map_err(|e| eyre::format_err!("{}", e))
+            tag_cache::versions(cask, &self.get_tags_remote(), is_refresh)
         }
     }
 
     // get the latest version of package
-    pub fn get_latest_version(&self) -> Result<Option<String>, Report> {
-        let version = self.get_versions()?;
+    pub fn get_latest_version(
+        &self,
+        cask: &cask::Cask,
+        is_refresh: bool,
+    ) -> Result<Option<String>, Report> {
+        let version = self.get_versions(cask, is_refresh)?;
 
         Ok(version.first().map(|f| f.to_string()))
     }
@@ -481,10 +1036,12 @@ impl<'a> Formula {
 
 #[cfg(test)]
 mod tests {
-    use std::env;
+    use std::{collections::HashMap, env, fs};
+
+    use url::Url;
 
-    use crate::cask;
     use crate::formula;
+    use crate::{address, cask, hooker};
 
     #[test]
     fn test_read_default_config() {
@@ -578,6 +1135,10 @@ mod tests {
                     arch.url,
                     "{package.repository}/releases/download/v{version}/gpm_linux_amd64.tar.gz"
                 );
+                assert_eq!(
+                    arch.mirrors.as_ref().unwrap(),
+                    &vec!["https://mirror.example.com/gpm/v{version}/gpm_linux_amd64.tar.gz"]
+                );
             }
             formula::ResourceTarget::Executable(_) => todo!(),
             formula::ResourceTarget::Simple(_) => todo!(),
@@ -592,6 +1153,62 @@ mod tests {
             formula::ResourceTarget::Executable(_) => todo!(),
             formula::ResourceTarget::Simple(_) => todo!(),
         }
+
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        {
+            let target = rc.get_current_download_url("0.1.12").unwrap();
+
+            assert_eq!(
+                target.urls(),
+                vec![
+                    "https://github.com/axetroy/gpm.rs/releases/download/v0.1.12/gpm_linux_amd64.tar.gz",
+                    "https://mirror.example.com/gpm/v0.1.12/gpm_linux_amd64.tar.gz"
+                ]
+            );
+            assert_eq!(
+                target.checksum_url.as_deref(),
+                Some("https://github.com/axetroy/gpm.rs/releases/download/v0.1.12/checksums.txt")
+            );
+            assert_eq!(target.filename().as_deref(), Some("gpm_linux_amd64.tar.gz"));
+        }
+    }
+
+    #[test]
+    fn test_resource_checksum() {
+        let single: formula::ResourceTargetDetail = toml::from_str(
+            r#"url = "https://example.com/v{version}/gpm.tar.gz"
+checksum = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824""#,
+        )
+        .unwrap();
+
+        let checksum = single.checksum.as_ref().unwrap();
+
+        assert_eq!(
+            checksum.get("0.1.12"),
+            Some("sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+
+        let versions: formula::ResourceTargetDetail = toml::from_str(
+            r#"url = "https://example.com/v{version}/gpm.tar.gz"
+checksum = { "0.1.12" = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824", "v0.1.11" = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9" }"#,
+        )
+        .unwrap();
+
+        let checksum = versions.checksum.as_ref().unwrap();
+
+        assert_eq!(
+            checksum.get("0.1.12"),
+            Some("sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(
+            checksum.get("v0.1.12"),
+            Some("sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(
+            checksum.get("0.1.11"),
+            Some("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+        assert_eq!(checksum.get("0.1.10"), None);
     }
 
     #[test]
@@ -687,17 +1304,393 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_fetch_from_git_url() {
+    #[tokio::test]
+    async fn test_fetch_from_git_url() {
         let root_dir = env::current_dir().unwrap().join("fixtures").join(".cask");
         let c = cask::new(&root_dir);
 
-        let formula =
-            formula::fetch(&c, "https://github.com/axetroy/prune.v", true, false).unwrap();
+        let formula = formula::fetch(&c, "https://github.com/axetroy/prune.v", None, true, false)
+            .await
+            .unwrap();
 
         assert_eq!(formula.package.name, "github.com/axetroy/prune.v")
     }
 
+    #[test]
+    fn test_get_versions_with_ssh() {
+        let config_path = env::current_dir()
+            .unwrap()
+            .join("fixtures")
+            .join("config")
+            .join("simple_Cask.toml");
+
+        let rc = formula::new(&config_path, "git@github.com:axetroy/gpm.rs.git").unwrap();
+
+        assert_eq!(rc.get_source_address(), "git@github.com:axetroy/gpm.rs.git");
+        assert_eq!(rc.get_tags_remote(), "git@github.com:axetroy/gpm.rs.git");
+
+        let rc = formula::new(&config_path, "").unwrap();
+
+        assert_eq!(rc.get_source_address(), "github.com/axetroy/gpm.rs");
+        assert_eq!(rc.get_tags_remote(), "https://github.com/axetroy/gpm.rs");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_local_path() {
+        let root_dir = env::current_dir().unwrap().join("fixtures").join(".cask");
+        let c = cask::new(&root_dir);
+
+        let config_path = env::current_dir()
+            .unwrap()
+            .join("fixtures")
+            .join("config")
+            .join("simple_Cask.toml");
+
+        let formula = formula::fetch(&c, "./fixtures/config/simple_Cask.toml", None, true, false)
+            .await
+            .unwrap();
+
+        assert_eq!(formula.package.name, "github.com/axetroy/gpm.rs");
+        assert_eq!(formula.filepath, config_path.canonicalize().unwrap());
+        assert_eq!(
+            formula.get_source_address(),
+            format!("{}", config_path.canonicalize().unwrap().display())
+        );
+
+        assert!(
+            formula::fetch(&c, "./fixtures/config/not_exist", None, true, false)
+                .await
+                .is_err()
+        );
+
+        // the bare relative filename of '--formula'
+        let formula = formula::fetch(
+            &c,
+            &address::to_local_address("fixtures/config/simple_Cask.toml"),
+            None,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(formula.filepath, config_path.canonicalize().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_subpath() {
+        let repo_dir = env::temp_dir().join("cask_test_fetch_from_subpath");
+        let formula_dir = repo_dir.join("formulas").join("gpm");
+
+        fs::remove_dir_all(&repo_dir).ok();
+        fs::create_dir_all(&formula_dir).unwrap();
+
+        fs::copy(
+            env::current_dir()
+                .unwrap()
+                .join("fixtures")
+                .join("config")
+                .join("simple_Cask.toml"),
+            formula_dir.join("Cask.toml"),
+        )
+        .unwrap();
+
+        let c = cask::new(&repo_dir.join(".cask"));
+
+        let repo_path = repo_dir.canonicalize().unwrap();
+
+        for addr in [
+            format!("{}//formulas/gpm", repo_path.display()),
+            format!("{}#formulas/gpm", repo_path.display()),
+        ] {
+            let formula = formula::fetch(&c, &addr, None, true, false).await.unwrap();
+
+            assert_eq!(formula.package.name, "github.com/axetroy/gpm.rs");
+            assert_eq!(formula.subpath.as_deref(), Some("formulas/gpm"));
+            assert_eq!(
+                formula.get_source_address(),
+                format!("{}//formulas/gpm", repo_path.display())
+            );
+        }
+
+        assert!(formula::fetch(
+            &c,
+            &format!("{}//formulas/not_exist", repo_path.display()),
+            None,
+            true,
+            false
+        )
+        .await
+        .is_err());
+
+        fs::remove_dir_all(&repo_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_local_bare_repository() {
+        let repo_dir = env::temp_dir().join("cask_test_local_bare_repository");
+        let work_dir = repo_dir.join("work");
+        let bare_dir = repo_dir.join("tool.git");
+
+        fs::remove_dir_all(&repo_dir).ok();
+        fs::create_dir_all(&work_dir).unwrap();
+
+        fs::copy(
+            env::current_dir()
+                .unwrap()
+                .join("fixtures")
+                .join("config")
+                .join("simple_Cask.toml"),
+            work_dir.join("Cask.toml"),
+        )
+        .unwrap();
+
+        let script = format!(
+            "git init -q && git add -A && git -c user.name=cask -c user.email=cask@example.com commit -q -m init && git clone -q --bare . {}",
+            bare_dir.display()
+        );
+
+        shell::run(
+            &work_dir,
+            &script,
+            &mut shell::Output::None,
+            HashMap::from([]),
+        )
+        .unwrap();
+
+        let c = cask::new(&repo_dir.join(".cask"));
+
+        let url = Url::from_file_path(&bare_dir).unwrap();
+
+        let formula = formula::fetch(&c, url.as_str(), None, true, false)
+            .await
+            .unwrap();
+
+        assert_eq!(formula.package.name, "github.com/axetroy/gpm.rs");
+        assert!(formula.get_source_address().starts_with("file://"));
+
+        fs::remove_dir_all(&repo_dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_run_in_formula_repository() {
+        let repo_dir = env::temp_dir().join("cask_test_hook_run_in_formula_repository");
+        let work_dir = repo_dir.join("work");
+        let formula_dir = work_dir.join("formulas").join("foo");
+        let bare_dir = repo_dir.join("tool.git");
+
+        fs::remove_dir_all(&repo_dir).ok();
+        fs::create_dir_all(&formula_dir).unwrap();
+
+        let content = fs::read_to_string(
+            env::current_dir()
+                .unwrap()
+                .join("fixtures")
+                .join("config")
+                .join("simple_Cask.toml"),
+        )
+        .unwrap();
+
+        fs::write(
+            formula_dir.join("Cask.toml"),
+            content + "\n[hook.unix.sh]\npostinstall = \"test -f install.sh\"\n",
+        )
+        .unwrap();
+        fs::write(formula_dir.join("install.sh"), "echo 'installed'").unwrap();
+
+        let script = format!(
+            "git init -q && git add -A && git -c user.name=cask -c user.email=cask@example.com commit -q -m init && git clone -q --bare . {}",
+            bare_dir.display()
+        );
+
+        shell::run(
+            &work_dir,
+            &script,
+            &mut shell::Output::None,
+            HashMap::from([]),
+        )
+        .unwrap();
+
+        let c = cask::new(&repo_dir.join(".cask"));
+
+        let url = Url::from_file_path(&bare_dir).unwrap();
+        let address = format!("{}//formulas/foo", url.as_str());
+
+        let formula = formula::fetch(&c, &address, None, false, false)
+            .await
+            .unwrap();
+
+        let hook_dir = formula.hook_dir(&c);
+
+        // the repository is cloned for the hooks, in the folder of declared package
+        assert_eq!(
+            hook_dir,
+            c.package_repository_dir(&formula.package.name)
+                .join("formulas")
+                .join("foo")
+        );
+        assert!(hook_dir.join("install.sh").exists());
+
+        formula
+            .hook
+            .as_ref()
+            .unwrap()
+            .run(
+                "postinstall",
+                &hook_dir,
+                HashMap::<String, String>::from([]),
+                &hooker::RunOption::default(),
+            )
+            .unwrap();
+
+        fs::remove_dir_all(&repo_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_formula_ref() {
+        let repo_dir = env::temp_dir().join("cask_test_fetch_with_formula_ref");
+        let work_dir = repo_dir.join("work");
+        let bare_dir = repo_dir.join("tool.git");
+
+        fs::remove_dir_all(&repo_dir).ok();
+        fs::create_dir_all(&work_dir).unwrap();
+
+        let content = fs::read_to_string(
+            env::current_dir()
+                .unwrap()
+                .join("fixtures")
+                .join("config")
+                .join("simple_Cask.toml"),
+        )
+        .unwrap();
+
+        let git_run = |script: &str| {
+            shell::run(
+                &work_dir,
+                script,
+                &mut shell::Output::None,
+                HashMap::from([]),
+            )
+            .unwrap();
+        };
+
+        let commit = |script: &str, versions: &str| {
+            git_run(script);
+
+            fs::write(
+                work_dir.join("Cask.toml"),
+                content.replace(r#"versions = ["0.1.12", "0.1.11"]"#, versions),
+            )
+            .unwrap();
+
+            git_run("git add -A && git -c user.name=cask -c user.email=cask@example.com commit -q -m update");
+        };
+
+        // the first commit of default branch
+        commit("git init -q", r#"versions = ["0.1.0"]"#);
+
+        let first_commit = git::head(&work_dir).unwrap();
+
+        commit("git checkout -q -b next", r#"versions = ["0.2.0"]"#);
+        commit("git checkout -q -", r#"versions = ["0.3.0"]"#);
+
+        git_run(&format!("git clone -q --bare . {}", bare_dir.display()));
+
+        let c = cask::new(&repo_dir.join(".cask"));
+
+        let url = Url::from_file_path(&bare_dir).unwrap();
+
+        let get_version = async |formula_ref: Option<&str>| {
+            let f = formula::fetch(&c, url.as_str(), formula_ref, true, false)
+                .await
+                .unwrap();
+
+            assert_eq!(f.formula_ref.as_deref(), formula_ref);
+            assert!(f.formula_commit.is_some());
+
+            (f.package.versions.unwrap()[0].clone(), f.formula_commit)
+        };
+
+        assert_eq!(get_version(None).await.0, "0.3.0");
+        assert_eq!(get_version(Some("next")).await.0, "0.2.0");
+        assert_eq!(
+            get_version(Some(&first_commit)).await,
+            ("0.1.0".to_string(), Some(first_commit.clone()))
+        );
+
+        assert!(
+            formula::fetch(&c, url.as_str(), Some("not_exist"), true, false)
+                .await
+                .is_err()
+        );
+
+        fs::remove_dir_all(&repo_dir).ok();
+    }
+
+    #[test]
+    fn test_get_formula_raw_url() {
+        let c = cask::new(&env::temp_dir().join("cask_test_get_formula_raw_url"));
+
+        assert_eq!(
+            formula::get_formula_raw_url(
+                &c,
+                "git@github.com:axetroy/gpm.rs.git",
+                "HEAD",
+                "formulas/Cask.toml"
+            )
+            .as_deref(),
+            Some("https://raw.githubusercontent.com/axetroy/gpm.rs/HEAD/formulas/Cask.toml")
+        );
+        assert_eq!(
+            formula::get_formula_raw_url(
+                &c,
+                "https://gitlab.com/org/tool.git",
+                "HEAD",
+                "Cask.toml"
+            ),
+            None
+        );
+        assert_eq!(
+            formula::get_formula_raw_url(&c, "file:///srv/git/tool.git", "HEAD", "Cask.toml"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_has_hook() {
+        assert!(formula::has_hook(
+            b"[package]\nname = \"foo\"\n\n[hook.unix.sh]\npostinstall = \"echo hello\"\n"
+        ));
+        assert!(!formula::has_hook(b"[package]\nname = \"foo\"\n"));
+        // clone the repository if the formula can not be parsed
+        assert!(formula::has_hook(b"invalid toml ["));
+    }
+
+    #[test]
+    fn test_cask_to_toml() {
+        let c = formula::Cask {
+            name: "github.com/axetroy/gpm.rs".to_string(),
+            created_at: "2022-03-12T10:01:49.938256+00:00".to_string(),
+            version: "0.1.12".to_string(),
+            repository: r#"C:\Users\axetroy\gpm.rs"#.to_string(),
+            subpath: None,
+            formula_ref: None,
+            formula_commit: None,
+        };
+
+        let content = c.to_toml().unwrap();
+
+        assert!(content.starts_with("[cask]\n"));
+
+        let f: toml::Value = toml::from_str(&content).unwrap();
+
+        assert_eq!(
+            f["cask"]["repository"].as_str().unwrap(),
+            r#"C:\Users\axetroy\gpm.rs"#
+        );
+    }
+
     #[test]
     fn test_get_hook() {
         let config_path = env::current_dir()
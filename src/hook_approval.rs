@@ -0,0 +1,280 @@
+#![deny(warnings)]
+
+use crate::{cask, hooker};
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+};
+
+use atty::{is, Stream};
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+
+// How to deal with the hooks of package that not approved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Prompt, // Show the scripts and ask for consent
+    Allow,  // Approve the scripts without asking. eg. --allow-scripts
+    Ignore, // Do not run the scripts. eg. --ignore-scripts
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct Approvals {
+    packages: HashMap<String, String>, // The package name and the hash of approved scripts
+}
+
+fn read_approvals(cask: &cask::Cask) -> Approvals {
+    fs::read_to_string(cask.approved_hooks_file())
+        .ok()
+        .and_then(|content| serde_json::from_str::<Approvals>(&content).ok())
+        .unwrap_or_default()
+}
+
+fn approve(cask: &cask::Cask, package_name: &str, digest: &str) -> Result<(), Report> {
+    let mut approvals = read_approvals(cask);
+
+    approvals
+        .packages
+        .insert(package_name.to_string(), digest.to_string());
+
+    fs::write(
+        cask.approved_hooks_file(),
+        serde_json::to_string_pretty(&approvals)?,
+    )?;
+
+    Ok(())
+}
+
+// remove the approval of package. eg. the package has been uninstalled
+pub fn revoke(cask: &cask::Cask, package_name: &str) -> Result<(), Report> {
+    let mut approvals = read_approvals(cask);
+
+    if approvals.packages.remove(package_name).is_some() {
+        fs::write(
+            cask.approved_hooks_file(),
+            serde_json::to_string_pretty(&approvals)?,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn confirm(msg: &str) -> Result<bool, Report> {
+    eprint!("{} [y/N] ", msg);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// How to ask user for consent, it's replaced in tests
+struct Prompter {
+    is_interactive: fn() -> bool, // Whether user can answer the question
+    confirm: fn(&str) -> Result<bool, Report>, // Ask the question and get the answer
+}
+
+const TERMINAL_PROMPTER: Prompter = Prompter {
+    is_interactive: || is(Stream::Stdin),
+    confirm,
+};
+
+// check whether the hooks of package can run.
+// the scripts are shown and confirmed the first time, and again once they changed
+pub fn check<C>(
+    cask: &cask::Cask,
+    package_name: &str,
+    hook: &hooker::Hook,
+    render_context: C,
+    permission: Permission,
+) -> Result<bool, Report>
+where
+    C: Serialize,
+{
+    check_with_prompter(
+        cask,
+        package_name,
+        hook,
+        render_context,
+        permission,
+        &TERMINAL_PROMPTER,
+    )
+}
+
+fn check_with_prompter<C>(
+    cask: &cask::Cask,
+    package_name: &str,
+    hook: &hooker::Hook,
+    render_context: C,
+    permission: Permission,
+    prompter: &Prompter,
+) -> Result<bool, Report>
+where
+    C: Serialize,
+{
+    let digest = match hook.digest() {
+        Some(d) => d,
+        None => return Ok(true),
+    };
+
+    if permission == Permission::Ignore {
+        eprintln!(
+            "Warning: skip the hooks of '{}' due to '--ignore-scripts'",
+            package_name
+        );
+        return Ok(false);
+    }
+
+    let approved = read_approvals(cask).packages.remove(package_name);
+
+    if approved.as_deref() == Some(digest.as_str()) {
+        return Ok(true);
+    }
+
+    if permission == Permission::Prompt {
+        if !(prompter.is_interactive)() {
+            return Err(eyre::format_err!(
+                "The hooks of '{}' are not approved. Run with '--allow-scripts' to run them or '--ignore-scripts' to skip them",
+                package_name
+            ));
+        }
+
+        if approved.is_some() {
+            eprintln!(
+                "The hooks of '{}' have changed since approved:",
+                package_name
+            );
+        } else {
+            eprintln!("The package '{}' wants to run the hooks:", package_name);
+        }
+
+        let envs = hook.render_env(&render_context)?;
+
+        if !envs.is_empty() {
+            eprintln!("\n[env]");
+
+            for (name, value) in envs {
+                eprintln!("{}={}", name, value);
+            }
+        }
+
+        for (name, script) in hook.render_all(&render_context)? {
+            eprintln!("\n[{}]\n{}", name, script.trim_end());
+        }
+
+        eprintln!();
+
+        if !(prompter.confirm)("Do you want to run the hooks?")? {
+            return Err(eyre::format_err!(
+                "The hooks of '{}' are not approved. Run with '--ignore-scripts' to install without them",
+                package_name
+            ));
+        }
+    }
+
+    approve(cask, package_name, &digest)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs};
+
+    use crate::{
+        cask,
+        hook_approval::{self, Permission, Prompter},
+        hooker::{Hook, HookDefinition, Terminal},
+    };
+
+    fn new_hook(script: &str) -> Hook {
+        let terminal = Terminal {
+            cmd: Some(HookDefinition {
+                preinstall: Some(script.to_string()),
+                ..Default::default()
+            }),
+            powershell: None,
+            sh: Some(HookDefinition {
+                preinstall: Some(script.to_string()),
+                ..Default::default()
+            }),
+            bash: None,
+            ..Default::default()
+        };
+
+        Hook {
+            windows: Some(terminal.clone()),
+            unix: Some(terminal),
+            linux: None,
+            macos: None,
+            freebsd: None,
+            env: None,
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_check_hook_approval() {
+        let root_dir = env::temp_dir().join("cask_test_check_hook_approval");
+
+        fs::remove_dir_all(&root_dir).ok();
+        fs::create_dir_all(&root_dir).unwrap();
+
+        let c = cask::new(&root_dir);
+        let ctx = HashMap::<String, String>::from([]);
+
+        let hook = new_hook("echo hello");
+
+        assert!(!hook_approval::check(&c, "dvm", &hook, &ctx, Permission::Ignore).unwrap());
+        assert!(hook_approval::check(&c, "dvm", &hook, &ctx, Permission::Allow).unwrap());
+
+        let non_interactive = Prompter {
+            is_interactive: || false,
+            confirm: |_| panic!("should not ask in non-interactive terminal"),
+        };
+        let reject = Prompter {
+            is_interactive: || true,
+            confirm: |_| Ok(false),
+        };
+        let accept = Prompter {
+            is_interactive: || true,
+            confirm: |_| Ok(true),
+        };
+
+        let check = |hook: &Hook, prompter: &Prompter| {
+            hook_approval::check_with_prompter(&c, "dvm", hook, &ctx, Permission::Prompt, prompter)
+        };
+
+        // the approved hooks run without asking
+        assert!(check(&hook, &non_interactive).unwrap());
+
+        // the changed hooks need approval again
+        let changed_hook = new_hook("echo world");
+
+        assert!(check(&changed_hook, &non_interactive).is_err());
+        assert!(check(&changed_hook, &reject).is_err());
+        assert_ne!(
+            hook_approval::read_approvals(&c).packages.get("dvm"),
+            changed_hook.digest().as_ref()
+        );
+
+        assert!(check(&changed_hook, &accept).unwrap());
+        assert_eq!(
+            hook_approval::read_approvals(&c).packages.get("dvm"),
+            changed_hook.digest().as_ref()
+        );
+
+        // the revoked hooks need approval again
+        hook_approval::revoke(&c, "dvm").unwrap();
+
+        assert!(!hook_approval::read_approvals(&c)
+            .packages
+            .contains_key("dvm"));
+        assert!(check(&changed_hook, &non_interactive).is_err());
+
+        fs::remove_dir_all(&root_dir).ok();
+    }
+}
@@ -1,23 +1,74 @@
 #![deny(warnings)]
 
-use std::{collections::HashMap, path::Path};
+use crate::{cask, formula, util::get_iso8601};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env::consts,
+    fs,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use eyre::Report;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tinytemplate::TinyTemplate;
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+// The names of hooks in the order of lifecycle
+pub const HOOK_NAMES: [&str; 6] = [
+    "preinstall",
+    "preupdate",
+    "postinstall",
+    "postupdate",
+    "preuninstall",
+    "postuninstall",
+];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct HookDefinition {
     pub preinstall: Option<String>, // The script will run before install package
     pub postinstall: Option<String>, // The script will run after install package
+    pub preuninstall: Option<String>, // The script will run before uninstall package
+    pub postuninstall: Option<String>, // The script will run after uninstall package
+    pub preupdate: Option<String>, // The script will run before update package, after the preinstall
+    pub postupdate: Option<String>, // The script will run after update package, after the postinstall
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct Terminal {
     pub cmd: Option<HookDefinition>,
     pub powershell: Option<HookDefinition>,
     pub sh: Option<HookDefinition>,
     pub bash: Option<HookDefinition>,
+    pub zsh: Option<HookDefinition>,
+    pub fish: Option<HookDefinition>,
+    pub pwsh: Option<HookDefinition>, // PowerShell Core, it's available on unix too
+    pub nushell: Option<HookDefinition>,
+}
+
+impl Terminal {
+    // the hooks of terminals in the order of preference on current platform
+    fn candidates(&self) -> Vec<(shell::Terminal, Option<&HookDefinition>)> {
+        if cfg!(target_os = "windows") {
+            vec![
+                (shell::Terminal::Cmd, self.cmd.as_ref()),
+                (shell::Terminal::PowerShell, self.powershell.as_ref()),
+                (shell::Terminal::Pwsh, self.pwsh.as_ref()),
+                (shell::Terminal::Nushell, self.nushell.as_ref()),
+            ]
+        } else {
+            vec![
+                (shell::Terminal::Sh, self.sh.as_ref()),
+                (shell::Terminal::Bash, self.bash.as_ref()),
+                (shell::Terminal::Zsh, self.zsh.as_ref()),
+                (shell::Terminal::Fish, self.fish.as_ref()),
+                (shell::Terminal::Pwsh, self.pwsh.as_ref()),
+                (shell::Terminal::Nushell, self.nushell.as_ref()),
+            ]
+        }
+    }
 }
 
 pub struct TerminalHook {
@@ -25,6 +76,23 @@ pub struct TerminalHook {
     pub hook: HookDefinition,
 }
 
+impl HookDefinition {
+    fn get_script(&self, hook_name: &str) -> Result<Option<&String>, Report> {
+        match hook_name {
+            "preinstall" => Ok(self.preinstall.as_ref()),
+            "postinstall" => Ok(self.postinstall.as_ref()),
+            "preuninstall" => Ok(self.preuninstall.as_ref()),
+            "postuninstall" => Ok(self.postuninstall.as_ref()),
+            "preupdate" => Ok(self.preupdate.as_ref()),
+            "postupdate" => Ok(self.postupdate.as_ref()),
+            _ => Err(eyre::format_err!(
+                "trying to run a unknown hook, names {}",
+                hook_name
+            )),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Hook {
     pub windows: Option<Terminal>,
@@ -32,90 +100,229 @@ pub struct Hook {
     pub linux: Option<Terminal>,
     pub macos: Option<Terminal>,
     pub freebsd: Option<Terminal>,
+    pub env: Option<BTreeMap<String, String>>, // The extra environment variables of scripts, the value can use the template
+    pub timeout: Option<u64>, // The seconds that a hook can run, it can not exceed the max timeout of config
+}
+
+// How many lines of log will be shown when the hook fail
+const LOG_TAIL_LINES: usize = 20;
+
+#[derive(Default)]
+pub struct RunOption {
+    pub envs: HashMap<String, String>, // The environment variables of cask, they take precedence over the formula
+    pub log_file: Option<PathBuf>, // Append the output of scripts to the file. Inherit the stdio if not provide
+    pub timeout: Option<Duration>, // Kill the script if it does not exit in time. No limit if not provide
+}
+
+// the option to run the hooks of package
+pub fn new_run_option(
+    cask: &cask::Cask,
+    hook: &Hook,
+    package: &formula::Package,
+    version: &str,
+    previous_version: Option<&str>,
+) -> Result<RunOption, Report> {
+    Ok(RunOption {
+        envs: environment(cask, package, version, previous_version),
+        log_file: Some(cask.hook_log_file(&package.name)),
+        timeout: Some(cask.config()?.hook_timeout(hook.timeout)),
+    })
+}
+
+// The environment variables that provided for the hook scripts
+pub fn environment(
+    cask: &cask::Cask,
+    package: &formula::Package,
+    version: &str,
+    previous_version: Option<&str>,
+) -> HashMap<String, String> {
+    let package_dir = cask.package_dir(&package.name);
+
+    #[cfg(unix)]
+    let executable_name = package.bin.clone();
+    #[cfg(windows)]
+    let executable_name = format!("{}.exe", &package.bin);
+
+    let mut envs = HashMap::from([
+        ("CASK_ROOT", cask.root_dir()),
+        ("CASK_BIN_DIR", cask.bin_dir()),
+        ("CASK_PACKAGE_DIR", package_dir.clone()),
+        (
+            "CASK_PACKAGE_BIN",
+            package_dir.join("bin").join(executable_name),
+        ),
+    ])
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), format!("{}", v.display())))
+    .collect::<HashMap<String, String>>();
+
+    envs.insert("CASK_PACKAGE_NAME".to_string(), package.name.clone());
+    envs.insert("CASK_VERSION".to_string(), version.to_string());
+    envs.insert("CASK_OS".to_string(), consts::OS.to_string());
+    envs.insert("CASK_ARCH".to_string(), consts::ARCH.to_string());
+
+    if let Some(v) = previous_version {
+        envs.insert("CASK_PREVIOUS_VERSION".to_string(), v.to_string());
+    }
+
+    envs
 }
 
 impl Hook {
-    pub fn resolve(&self) -> Option<TerminalHook> {
-        let terminal = {
-            if cfg!(unix) {
-                if cfg!(target_os = "linux") {
-                    if self.linux.is_some() {
-                        self.linux.as_ref()
-                    } else {
-                        self.unix.as_ref()
-                    }
-                } else if cfg!(target_os = "macos") {
-                    if self.macos.is_some() {
-                        self.macos.as_ref()
-                    } else {
-                        self.unix.as_ref()
-                    }
-                } else if cfg!(target_os = "freebsd") {
-                    if self.freebsd.is_some() {
-                        self.freebsd.as_ref()
-                    } else {
-                        self.unix.as_ref()
-                    }
+    // get the terminals of current platform
+    fn terminal(&self) -> Option<&Terminal> {
+        if cfg!(unix) {
+            if cfg!(target_os = "linux") {
+                if self.linux.is_some() {
+                    self.linux.as_ref()
+                } else {
+                    self.unix.as_ref()
+                }
+            } else if cfg!(target_os = "macos") {
+                if self.macos.is_some() {
+                    self.macos.as_ref()
+                } else {
+                    self.unix.as_ref()
+                }
+            } else if cfg!(target_os = "freebsd") {
+                if self.freebsd.is_some() {
+                    self.freebsd.as_ref()
                 } else {
                     self.unix.as_ref()
                 }
             } else {
-                self.windows.as_ref()
+                self.unix.as_ref()
             }
-        };
+        } else {
+            self.windows.as_ref()
+        }
+    }
 
-        if let Some(t) = terminal {
-            if cfg!(target_os = "windows") {
-                t.clone()
-                    .cmd
-                    .map(|hook| TerminalHook {
-                        terminal: shell::Terminal::Cmd,
-                        hook,
-                    })
-                    .or_else(|| {
-                        t.clone().powershell.map(|hook| TerminalHook {
-                            terminal: shell::Terminal::PowerShell,
-                            hook,
-                        })
-                    })
-            } else {
-                t.clone()
-                    .sh
-                    .map(|hook| TerminalHook {
-                        terminal: shell::Terminal::Sh,
-                        hook,
-                    })
-                    .or_else(|| {
-                        t.clone().bash.map(|hook| TerminalHook {
-                            terminal: shell::Terminal::Bash,
-                            hook,
-                        })
-                    })
+    // get the hook of the first available terminal which defined the hook
+    pub fn resolve(&self) -> Option<TerminalHook> {
+        self.terminal()?
+            .candidates()
+            .into_iter()
+            .filter_map(|(terminal, hook)| hook.map(|h| (terminal, h)))
+            .find(|(terminal, _)| terminal.is_available())
+            .map(|(terminal, hook)| TerminalHook {
+                terminal,
+                hook: hook.clone(),
+            })
+    }
+
+    // whether the hook defines the script on current platform
+    pub fn has_script(&self, hook_name: &str) -> bool {
+        self.resolve()
+            .map(|t| matches!(t.hook.get_script(hook_name), Ok(Some(_))))
+            .unwrap_or(false)
+    }
+
+    // the hash of scripts that will run on current platform, it changes if any script changed.
+    // returns None if no script
+    pub fn digest(&self) -> Option<String> {
+        let terminal_hook = self.resolve()?;
+
+        let mut hasher = Sha256::new();
+        let mut has_script = false;
+
+        hasher.update(format!("{:?}", terminal_hook.terminal));
+
+        // the environment variables change the behavior of scripts too
+        for (key, value) in self.env.iter().flatten() {
+            hasher.update(format!("\0{}={}", key, value));
+        }
+
+        for name in HOOK_NAMES {
+            if let Ok(Some(script)) = terminal_hook.hook.get_script(name) {
+                has_script = true;
+                hasher.update(format!("\0{}\0{}", name, script));
             }
+        }
+
+        if has_script {
+            Some(format!("{:x}", hasher.finalize()))
         } else {
             None
         }
     }
 
-    pub fn run<C>(&self, hook_name: &str, cwd: &Path, render_context: C) -> Result<(), Report>
+    // render the scripts that will run on current platform, for review
+    pub fn render_all<C>(&self, render_context: C) -> Result<Vec<(&str, String)>, Report>
+    where
+        C: Serialize,
+    {
+        let mut scripts = vec![];
+
+        if let Some(terminal_hook) = self.resolve() {
+            for name in HOOK_NAMES {
+                if let Some(script) = terminal_hook.hook.get_script(name)? {
+                    let mut tt = TinyTemplate::new();
+
+                    tt.add_template(name, script)?;
+
+                    scripts.push((name, tt.render(name, &render_context)?));
+                }
+            }
+        }
+
+        Ok(scripts)
+    }
+
+    // render the extra environment variables that declared in the formula
+    pub fn render_env<C>(&self, render_context: C) -> Result<BTreeMap<String, String>, Report>
+    where
+        C: Serialize,
+    {
+        let mut envs = BTreeMap::new();
+
+        for (key, value) in self.env.iter().flatten() {
+            let mut tt = TinyTemplate::new();
+
+            tt.add_template(key, value)?;
+
+            envs.insert(key.clone(), tt.render(key, &render_context)?);
+        }
+
+        Ok(envs)
+    }
+
+    pub fn run<C>(
+        &self,
+        hook_name: &str,
+        cwd: &Path,
+        render_context: C,
+        option: &RunOption,
+    ) -> Result<(), Report>
     where
         C: Serialize,
     {
         let hook_op = self.resolve();
 
+        // the hooks are defined but none of the terminals is installed
+        if hook_op.is_none() {
+            if let Some(t) = self.terminal() {
+                let terminals = t
+                    .candidates()
+                    .into_iter()
+                    .filter(|(_, hook)| hook.is_some())
+                    .map(|(terminal, _)| format!("{:?}", terminal))
+                    .collect::<Vec<String>>();
+
+                if !terminals.is_empty() {
+                    return Err(eyre::format_err!(
+                        "can not run the '{}' hook, require one of the terminals: {}",
+                        hook_name,
+                        terminals.join(", ")
+                    ));
+                }
+            }
+        }
+
         if let Some(terminal_hook) = hook_op {
             let hook = terminal_hook.hook;
 
-            let script_op = match hook_name {
-                "preinstall" => Ok(&hook.preinstall),
-                "postinstall" => Ok(&hook.postinstall),
-                _ => Err(eyre::format_err!(
-                    "trying to run a unknown hook, names {}",
-                    hook_name
-                )),
-            }?;
-
-            if let Some(script) = script_op {
+            if let Some(script) = hook.get_script(hook_name)? {
                 eprintln!("Running '{}' hook", hook_name);
 
                 let mut tt = TinyTemplate::new();
@@ -124,13 +331,31 @@ impl Hook {
 
                 let renderer_script = tt.render(hook_name, &render_context)?;
 
-                shell::run_with(
-                    terminal_hook.terminal,
-                    cwd,
-                    &renderer_script,
-                    &mut shell::Output::Inherit,
-                    HashMap::from([]),
-                )?;
+                let mut hook_envs: HashMap<String, String> =
+                    self.render_env(&render_context)?.into_iter().collect();
+
+                hook_envs.extend(option.envs.clone());
+
+                match &option.log_file {
+                    Some(log_file_path) => run_with_log(
+                        terminal_hook.terminal,
+                        hook_name,
+                        cwd,
+                        &renderer_script,
+                        hook_envs,
+                        log_file_path,
+                        option.timeout,
+                    )?,
+                    None => shell::run_with_timeout(
+                        terminal_hook.terminal,
+                        cwd,
+                        &renderer_script,
+                        &mut shell::Output::Inherit,
+                        hook_envs,
+                        option.timeout,
+                    )
+                    .map_err(|e| eyre::format_err!("the '{}' hook fail: {}", hook_name, e))?,
+                }
             }
         }
 
@@ -138,11 +363,67 @@ impl Hook {
     }
 }
 
+// run the script and append the output to the log file, show the tail of log if fail
+fn run_with_log(
+    terminal: shell::Terminal,
+    hook_name: &str,
+    cwd: &Path,
+    script: &str,
+    envs: HashMap<String, String>,
+    log_file_path: &Path,
+    timeout: Option<Duration>,
+) -> Result<(), Report> {
+    if let Some(dir) = log_file_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut log_file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file_path)?;
+
+    let start_at = log_file.seek(SeekFrom::End(0))? as usize;
+
+    writeln!(log_file, "[{}] Running '{}' hook", get_iso8601(), hook_name)?;
+
+    let result = shell::run_with_timeout(
+        terminal,
+        cwd,
+        script,
+        &mut shell::Output::Tee(&log_file),
+        envs,
+        timeout,
+    );
+
+    if let Err(e) = result {
+        writeln!(log_file, "[{}] Fail: {}", get_iso8601(), e)?;
+
+        let content = fs::read(log_file_path)?;
+        let output = String::from_utf8_lossy(&content[start_at.min(content.len())..]).to_string();
+        let lines = output.lines().collect::<Vec<&str>>();
+        let tail = lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n");
+
+        return Err(eyre::format_err!(
+            "the '{}' hook fail: {}\nThe log file: {}\n\n{}",
+            hook_name,
+            e,
+            log_file_path.display(),
+            tail
+        ));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, env};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        env, fs,
+        time::Duration,
+    };
 
-    use crate::hooker::{self, HookDefinition, Terminal};
+    use crate::hooker::{self, HookDefinition, RunOption, Terminal};
 
     #[test]
     fn test_run_hooker() {
@@ -154,10 +435,12 @@ mod tests {
                 cmd: Some(HookDefinition {
                     preinstall: Some(preinstall_script.clone()),
                     postinstall: Some(postinstall_script.clone()),
+                    ..Default::default()
                 }),
                 powershell: None,
                 sh: None,
                 bash: None,
+                ..Default::default()
             }),
             unix: Some(Terminal {
                 cmd: None,
@@ -165,18 +448,23 @@ mod tests {
                 sh: Some(HookDefinition {
                     preinstall: Some(preinstall_script),
                     postinstall: Some(postinstall_script),
+                    ..Default::default()
                 }),
                 bash: None,
+                ..Default::default()
             }),
             linux: None,
             macos: None,
             freebsd: None,
+            env: None,
+            timeout: None,
         };
 
         let r1 = hook.run(
             "preinstall",
             &env::current_dir().unwrap(),
             HashMap::<String, String>::from([]),
+            &RunOption::default(),
         );
 
         assert!(r1.is_ok());
@@ -185,6 +473,7 @@ mod tests {
             "postinstall",
             &env::current_dir().unwrap(),
             HashMap::<String, String>::from([]),
+            &RunOption::default(),
         );
 
         assert!(r2.is_ok());
@@ -193,8 +482,250 @@ mod tests {
             "unknown",
             &env::current_dir().unwrap(),
             HashMap::<String, String>::from([]),
+            &RunOption::default(),
         );
 
         assert!(r3.is_err());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_update_hooker() {
+        let hook = hooker::Hook {
+            windows: None,
+            unix: Some(Terminal {
+                cmd: None,
+                powershell: None,
+                sh: Some(HookDefinition {
+                    preupdate: Some(
+                        r#"test "{version}" = "2.0.0" && test "{previous_version}" = "1.0.0""#
+                            .to_string(),
+                    ),
+                    postuninstall: Some("exit 1".to_string()),
+                    ..Default::default()
+                }),
+                bash: None,
+                ..Default::default()
+            }),
+            linux: None,
+            macos: None,
+            freebsd: None,
+            env: None,
+            timeout: None,
+        };
+
+        assert!(hook.has_script("preupdate"));
+        assert!(hook.has_script("postuninstall"));
+        assert!(!hook.has_script("preuninstall"));
+
+        let cwd = env::current_dir().unwrap();
+
+        hook.run(
+            "preupdate",
+            &cwd,
+            HashMap::from([("version", "2.0.0"), ("previous_version", "1.0.0")]),
+            &RunOption::default(),
+        )
+        .unwrap();
+
+        assert!(hook
+            .run(
+                "preupdate",
+                &cwd,
+                HashMap::from([("version", "2.0.0"), ("previous_version", "1.1.0")]),
+                &RunOption::default(),
+            )
+            .is_err());
+
+        // the hook does not defined
+        hook.run(
+            "postupdate",
+            &cwd,
+            HashMap::<String, String>::from([]),
+            &RunOption::default(),
+        )
+        .unwrap();
+
+        assert!(hook
+            .run(
+                "postuninstall",
+                &cwd,
+                HashMap::<String, String>::from([]),
+                &RunOption::default()
+            )
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hooker_with_env() {
+        let hook = hooker::Hook {
+            windows: None,
+            unix: Some(Terminal {
+                cmd: None,
+                powershell: None,
+                sh: Some(HookDefinition {
+                    postinstall: Some(
+                        r#"test "$CASK_VERSION" = "1.0.0" && test "$GREETING" = "hello 1.0.0""#
+                            .to_string(),
+                    ),
+                    ..Default::default()
+                }),
+                bash: None,
+                ..Default::default()
+            }),
+            linux: None,
+            macos: None,
+            freebsd: None,
+            env: Some(BTreeMap::from([(
+                "GREETING".to_string(),
+                "hello {version}".to_string(),
+            )])),
+            timeout: None,
+        };
+
+        let cwd = env::current_dir().unwrap();
+        let ctx = HashMap::from([("version", "1.0.0")]);
+
+        hook.run(
+            "postinstall",
+            &cwd,
+            &ctx,
+            &RunOption {
+                envs: HashMap::from([("CASK_VERSION".to_string(), "1.0.0".to_string())]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(hook
+            .run(
+                "postinstall",
+                &cwd,
+                &ctx,
+                &RunOption {
+                    envs: HashMap::from([("CASK_VERSION".to_string(), "2.0.0".to_string())]),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hooker_with_log() {
+        let hook = hooker::Hook {
+            windows: None,
+            unix: Some(Terminal {
+                cmd: None,
+                powershell: None,
+                sh: Some(HookDefinition {
+                    preinstall: Some("echo 'preparing'".to_string()),
+                    postinstall: Some("echo 'something wrong' && exit 2".to_string()),
+                    preuninstall: Some("sleep 5".to_string()),
+                    ..Default::default()
+                }),
+                bash: None,
+                ..Default::default()
+            }),
+            linux: None,
+            macos: None,
+            freebsd: None,
+            env: None,
+            timeout: None,
+        };
+
+        let cwd = env::current_dir().unwrap();
+        let log_file = env::temp_dir()
+            .join("cask_test_run_hooker_with_log")
+            .join("hook.log");
+
+        fs::remove_file(&log_file).ok();
+
+        let option = RunOption {
+            log_file: Some(log_file.clone()),
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        let ctx = HashMap::<String, String>::from([]);
+
+        hook.run("preinstall", &cwd, &ctx, &option).unwrap();
+
+        let err = hook
+            .run("postinstall", &cwd, &ctx, &option)
+            .unwrap_err()
+            .to_string();
+
+        // only show the log of failed hook
+        assert!(err.contains("something wrong"));
+        assert!(!err.contains("preparing"));
+        assert!(err.contains(&format!("{}", log_file.display())));
+
+        let err = hook
+            .run("preuninstall", &cwd, &ctx, &option)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("timeout"));
+
+        let content = fs::read_to_string(&log_file).unwrap();
+
+        assert!(content.contains("preparing"));
+        assert!(content.contains("something wrong"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_fallback_terminal() {
+        let definition = HookDefinition {
+            postinstall: Some("echo 'hello'".to_string()),
+            ..Default::default()
+        };
+
+        // the preferred terminal is sh
+        let hook = hooker::Hook {
+            windows: None,
+            unix: Some(Terminal {
+                pwsh: Some(definition.clone()),
+                sh: Some(definition.clone()),
+                ..Default::default()
+            }),
+            linux: None,
+            macos: None,
+            freebsd: None,
+            env: None,
+            timeout: None,
+        };
+
+        assert_eq!(hook.resolve().unwrap().terminal, shell::Terminal::Sh);
+
+        // fallback to the next terminal if the prior one does not installed
+        let hook = hooker::Hook {
+            unix: Some(Terminal {
+                fish: Some(definition.clone()),
+                nushell: Some(definition),
+                ..Default::default()
+            }),
+            ..hook
+        };
+
+        let cwd = env::current_dir().unwrap();
+        let ctx = HashMap::<String, String>::from([]);
+
+        match hook.resolve() {
+            Some(terminal_hook) => {
+                assert!(terminal_hook.terminal.is_available());
+                hook.run("postinstall", &cwd, &ctx, &RunOption::default())
+                    .unwrap();
+            }
+            None => {
+                assert!(!shell::Terminal::Fish.is_available());
+                assert!(!shell::Terminal::Nushell.is_available());
+                assert!(hook
+                    .run("postinstall", &cwd, &ctx, &RunOption::default())
+                    .is_err());
+            }
+        }
+    }
 }
@@ -1,6 +1,10 @@
 #![deny(warnings)]
 
+mod address;
 mod cask;
+mod command_cache_clear;
+mod command_cache_list;
+mod command_cache_prune;
 mod command_check_updates;
 mod command_clean;
 mod command_homepage;
@@ -14,15 +18,33 @@ mod command_self_uninstall;
 mod command_self_update;
 mod command_uninstall;
 mod command_update;
+mod config;
+mod credentials;
+mod download_cache;
 mod formula;
+mod hook_approval;
 mod hooker;
+mod policy;
+mod signature;
 mod symlink;
+mod tag_cache;
 mod util;
 
-use std::process;
+use std::{process, time::Duration};
 
 use atty::{is, Stream};
-use clap::{arg, crate_version, Arg, Command};
+use clap::{arg, crate_version, Arg, ArgMatches, Command};
+
+// how to deal with the hooks of package which not approved
+fn get_script_permission(matches: &ArgMatches) -> hook_approval::Permission {
+    if matches.contains_id("allow-scripts") {
+        hook_approval::Permission::Allow
+    } else if matches.contains_id("ignore-scripts") {
+        hook_approval::Permission::Ignore
+    } else {
+        hook_approval::Permission::Prompt
+    }
+}
 
 #[tokio::main]
 async fn main() {
@@ -38,9 +60,9 @@ async fn main() {
                 .about("Install package")
                 .arg(
                     Arg::new("PACKAGE")
-                        .required(is(Stream::Stdin))
+                        .required(false)
                         .num_args(1)
-                        .help("The package name or repository url"),
+                        .help("The package name, repository url or local path"),
                 )
                 .arg(
                     Arg::new("VERSION")
@@ -48,6 +70,18 @@ async fn main() {
                         .num_args(0..=1)
                         .help("Install specified version."),
                 )
+                .arg(
+                    Arg::new("formula")
+                        .long("formula")
+                        .num_args(1)
+                        .help("Install from the local formula file. eg. cask install --formula ./Cask.toml [VERSION]"),
+                )
+                .arg(
+                    Arg::new("formula-ref")
+                        .long("formula-ref")
+                        .num_args(1)
+                        .help("Fetch the formula from the branch, tag or commit of the repository"),
+                )
                 .arg(
                     Arg::new("verbose")
                         .short('v')
@@ -55,6 +89,25 @@ async fn main() {
                         .help("Print verbose information")
                         .num_args(0..=1),
                 )
+                .arg(
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .help("Ignore the cached remote tags and fetch them again")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("allow-scripts")
+                        .long("allow-scripts")
+                        .help("Run the hooks of package without asking for consent")
+                        .conflicts_with("ignore-scripts")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .help("Do not run the hooks of package")
+                        .num_args(0..=1),
+                )
                 .arg_required_else_help(is(Stream::Stdin)),
         )
         .subcommand(
@@ -62,6 +115,19 @@ async fn main() {
                 .visible_alias("rm")
                 .about("Uninstall package")
                 .arg(arg!(<PACKAGE> "The package name or the executable file name of the package"))
+                .arg(
+                    Arg::new("allow-scripts")
+                        .long("allow-scripts")
+                        .help("Run the hooks of package without asking for consent")
+                        .conflicts_with("ignore-scripts")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .help("Do not run the hooks of package")
+                        .num_args(0..=1),
+                )
                 .arg_required_else_help(true),
         )
         .subcommand(
@@ -80,6 +146,12 @@ async fn main() {
             Command::new("info")
                 .about("Show information of package")
                 .arg(arg!(<PACKAGE> "The package name"))
+                .arg(
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .help("Ignore the cached remote tags and fetch them again")
+                        .num_args(0..=1),
+                )
                 .arg_required_else_help(true),
         )
         .subcommand(
@@ -101,6 +173,25 @@ async fn main() {
                         .help("Print verbose information")
                         .num_args(0..=1),
                 )
+                .arg(
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .help("Ignore the cached remote tags and fetch them again")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("allow-scripts")
+                        .long("allow-scripts")
+                        .help("Run the hooks of package without asking for consent")
+                        .conflicts_with("ignore-scripts")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .help("Do not run the hooks of package")
+                        .num_args(0..=1),
+                )
                 .arg_required_else_help(true),
         )
         .subcommand(
@@ -127,6 +218,25 @@ async fn main() {
                         .long("verbose")
                         .help("Print verbose information")
                         .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .help("Ignore the cached remote tags and fetch them again")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("allow-scripts")
+                        .long("allow-scripts")
+                        .help("Run the hooks of package without asking for consent")
+                        .conflicts_with("ignore-scripts")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("ignore-scripts")
+                        .long("ignore-scripts")
+                        .help("Do not run the hooks of package")
+                        .num_args(0..=1),
                 ),
         )
         .subcommand(
@@ -169,6 +279,41 @@ async fn main() {
                                 .num_args(0..=1),
                         ),
                 ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Operation for download cache")
+                .subcommand(
+                    Command::new("list")
+                        .visible_alias("ls")
+                        .about("List the cached files")
+                        .arg(
+                            Arg::new("json")
+                                .short('j')
+                                .long("json")
+                                .help("Print json format instead of pretty format")
+                                .num_args(0..=1),
+                        ),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Remove the cached files over the limit")
+                        .arg(
+                            Arg::new("max-size")
+                                .long("max-size")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(u64))
+                                .help("The max bytes of the cache, the least recently used files are removed first"),
+                        )
+                        .arg(
+                            Arg::new("max-age")
+                                .long("max-age")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(u64))
+                                .help("Remove the files unused longer than the seconds"),
+                        ),
+                )
+                .subcommand(Command::new("clear").about("Remove all the cached files")),
         );
 
     let matches = app.clone().get_matches();
@@ -186,19 +331,42 @@ async fn main() {
 
     match matches.subcommand() {
         Some(("install", sub_matches)) => {
-            let package_name = sub_matches.get_one::<String>("PACKAGE").expect("required");
+            let formula_file = sub_matches.get_one::<String>("formula");
+            let package = sub_matches.get_one::<String>("PACKAGE");
 
-            let version = sub_matches.get_one::<String>("VERSION").map(|x| x.as_str());
+            // the package can be empty if the formula is read from stdin
+            // the first positional argument is the version if the formula file provided
+            // the formula file is always a local path, even it's a bare filename. eg. Cask.toml
+            let formula_address = formula_file.map(|f| address::to_local_address(f));
+            let (package_name, version) = match &formula_address {
+                Some(f) => (f.as_str(), package.map(|x| x.as_str())),
+                None => (
+                    package.map(|x| x.as_str()).unwrap_or(""),
+                    sub_matches.get_one::<String>("VERSION").map(|x| x.as_str()),
+                ),
+            };
+            let formula_ref = sub_matches
+                .get_one::<String>("formula-ref")
+                .map(|x| x.as_str());
             let is_verbose = sub_matches.contains_id("verbose");
+            let is_refresh = sub_matches.contains_id("refresh");
 
-            command_install::install(&cask, package_name, version, is_verbose)
-                .await
-                .expect("install package fail!");
+            command_install::install(
+                &cask,
+                package_name,
+                version,
+                formula_ref,
+                is_verbose,
+                is_refresh,
+                get_script_permission(sub_matches),
+            )
+            .await
+            .expect("install package fail!");
         }
         Some(("uninstall", sub_matches)) => {
             let package_name = sub_matches.get_one::<String>("PACKAGE").expect("required");
 
-            command_uninstall::uninstall(&cask, package_name)
+            command_uninstall::uninstall(&cask, package_name, get_script_permission(sub_matches))
                 .await
                 .expect("uninstall package fail!");
         }
@@ -211,7 +379,9 @@ async fn main() {
         Some(("info", sub_matches)) => {
             let package_name = sub_matches.get_one::<String>("PACKAGE").expect("required");
 
-            command_info::info(&cask, package_name)
+            let is_refresh = sub_matches.contains_id("refresh");
+
+            command_info::info(&cask, package_name, is_refresh)
                 .await
                 .expect("info installed package fail!");
         }
@@ -219,10 +389,18 @@ async fn main() {
             let package_name = sub_matches.get_one::<String>("PACKAGE").expect("required");
             let is_check_only = sub_matches.contains_id("check-only");
             let is_verbose = sub_matches.contains_id("verbose");
+            let is_refresh = sub_matches.contains_id("refresh");
 
-            command_update::update(&cask, package_name, is_check_only, is_verbose)
-                .await
-                .expect("update package fail!");
+            command_update::update(
+                &cask,
+                package_name,
+                is_check_only,
+                is_verbose,
+                is_refresh,
+                get_script_permission(sub_matches),
+            )
+            .await
+            .expect("update package fail!");
         }
         Some(("homepage", sub_matches)) => {
             let package_name = sub_matches.get_one::<String>("PACKAGE").expect("required");
@@ -234,10 +412,17 @@ async fn main() {
         Some(("check-updates", sub_matches)) => {
             let is_check_only = sub_matches.contains_id("check-only");
             let is_verbose = sub_matches.contains_id("verbose");
+            let is_refresh = sub_matches.contains_id("refresh");
 
-            command_check_updates::check_updates(&cask, is_check_only, is_verbose)
-                .await
-                .expect("check-updates of packages fail!");
+            command_check_updates::check_updates(
+                &cask,
+                is_check_only,
+                is_verbose,
+                is_refresh,
+                get_script_permission(sub_matches),
+            )
+            .await
+            .expect("check-updates of packages fail!");
         }
         Some(("clean", _sub_matches)) => {
             command_clean::clean(&cask).await.expect("clean fail!");
@@ -270,6 +455,33 @@ async fn main() {
                 process::exit(0x1);
             }
         },
+        Some(("cache", sub_matches)) => match sub_matches.subcommand() {
+            Some(("list", list_sub_matches)) => {
+                let is_print_as_json = list_sub_matches.contains_id("json");
+                command_cache_list::list(&cask, is_print_as_json)
+                    .await
+                    .expect("list download cache fail!");
+            }
+            Some(("prune", prune_sub_matches)) => {
+                let max_size = prune_sub_matches.get_one::<u64>("max-size").copied();
+                let max_age = prune_sub_matches
+                    .get_one::<u64>("max-age")
+                    .map(|s| Duration::from_secs(*s));
+                command_cache_prune::prune(&cask, max_size, max_age)
+                    .await
+                    .expect("prune download cache fail!");
+            }
+            Some(("clear", _clear_sub_matches)) => {
+                command_cache_clear::clear(&cask)
+                    .await
+                    .expect("clear download cache fail!");
+            }
+            _ => {
+                let sub_cmd = app.find_subcommand_mut("cache").unwrap();
+                sub_cmd.print_help().unwrap();
+                process::exit(0x1);
+            }
+        },
         Some((ext, sub_matches)) => {
             let args = sub_matches
                 .get_many::<String>("")
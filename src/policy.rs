@@ -0,0 +1,329 @@
+#![deny(warnings)]
+
+use crate::cask;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Policy {
+    #[serde(skip)]
+    pub filepath: PathBuf, // The file of policy, it's shown in the error message
+    pub allow_hosts: Option<Vec<String>>, // Only the packages from the hosts can be installed. eg. ["github.com", "*.corp.example.com"]
+    pub deny_hosts: Option<Vec<String>>,  // The packages from the hosts can not be installed
+    pub allow_packages: Option<Vec<String>>, // Only the packages match the patterns can be installed. eg. ["github.com/axetroy/*"]
+    pub deny_packages: Option<Vec<String>>,  // The packages match the patterns can not be installed
+    pub allow_local: Option<bool>, // Whether the package can be installed from local path or stdin. default: true
+    pub require_checksum: Option<bool>, // Refuse to install the package without checksum
+    pub require_signature: Option<bool>, // Refuse to install the package without signature
+    pub disallow_hooks: Option<bool>, // Refuse to install the package with hooks
+}
+
+// The policies of system and user, all of them must be satisfied
+pub struct Policies {
+    policies: Vec<Policy>,
+}
+
+// the policy managed by administrator
+pub fn system_policy_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        PathBuf::from(
+            std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string()),
+        )
+        .join("cask")
+        .join("policy.toml")
+    }
+    #[cfg(not(windows))]
+    {
+        PathBuf::from("/etc/cask/policy.toml")
+    }
+}
+
+fn load_file(policy_file: &Path) -> Result<Option<Policy>, Report> {
+    if !policy_file.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(policy_file)?;
+
+    let mut policy: Policy = toml::from_str(&content).map_err(|e| {
+        eyre::format_err!("parse policy file '{}' fail: {}", policy_file.display(), e)
+    })?;
+
+    policy.filepath = policy_file.to_path_buf();
+
+    Ok(Some(policy))
+}
+
+// load the policies of system and user.
+// the user policy can only add restrictions, it can never replace the system policy
+pub fn load(cask: &cask::Cask) -> Result<Policies, Report> {
+    load_with(cask, &system_policy_file())
+}
+
+fn load_with(cask: &cask::Cask, system_policy_file: &Path) -> Result<Policies, Report> {
+    let mut policies = vec![];
+
+    for file in [system_policy_file.to_path_buf(), cask.policy_file()] {
+        if let Some(policy) = load_file(&file)? {
+            policies.push(policy)
+        }
+    }
+
+    // the 'security.require_signature' of config is a restriction of user, it's checked as the policy
+    let config = cask.config()?;
+
+    if config.security.and_then(|s| s.require_signature) == Some(true) {
+        policies.push(Policy {
+            filepath: cask.config_file(),
+            require_signature: Some(true),
+            ..Default::default()
+        })
+    }
+
+    Ok(Policies { policies })
+}
+
+// whether the text matches the pattern, the '*' matches any characters
+pub fn is_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+
+    let parts: Vec<&str> = pattern.split('*').collect();
+
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+
+    if !text.starts_with(first) || !text.ends_with(last) || text.len() < first.len() + last.len() {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..text.len() - last.len()];
+
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+fn is_any_match(patterns: &Option<Vec<String>>, text: &str) -> bool {
+    patterns
+        .as_ref()
+        .map(|list| list.iter().any(|p| is_match(p, text)))
+        .unwrap_or(false)
+}
+
+impl Policies {
+    // check the host and name of the package source. eg. github.com/axetroy/dvm
+    pub fn check_source(&self, source: &str) -> Result<(), Report> {
+        let host = source.split('/').next().unwrap_or_default();
+
+        for p in &self.policies {
+            if is_any_match(&p.deny_hosts, host)
+                || (p.allow_hosts.is_some() && !is_any_match(&p.allow_hosts, host))
+            {
+                return Err(eyre::format_err!(
+                    "The host '{}' is not allowed by the policy '{}'. Check 'allow_hosts' and 'deny_hosts' of the policy or contact your administrator",
+                    host,
+                    p.filepath.display()
+                ));
+            }
+        }
+
+        self.check_package(source)
+    }
+
+    // check the package installed from local path or stdin
+    pub fn check_local(&self, path: &str) -> Result<(), Report> {
+        for p in &self.policies {
+            if p.allow_local == Some(false) {
+                return Err(eyre::format_err!(
+                    "Install the package from local '{}' is not allowed by the policy '{}'. Install it from the allowed hosts instead",
+                    path,
+                    p.filepath.display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // check the name of package. eg. github.com/axetroy/dvm
+    pub fn check_package(&self, name: &str) -> Result<(), Report> {
+        for p in &self.policies {
+            if is_any_match(&p.deny_packages, name)
+                || (p.allow_packages.is_some() && !is_any_match(&p.allow_packages, name))
+            {
+                return Err(eyre::format_err!(
+                    "The package '{}' is not allowed by the policy '{}'. Check 'allow_packages' and 'deny_packages' of the policy or contact your administrator",
+                    name,
+                    p.filepath.display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // check the package before install
+    pub fn check_install(
+        &self,
+        name: &str,
+        has_checksum: bool,
+        has_signature: bool,
+        has_hook: bool,
+    ) -> Result<(), Report> {
+        for p in &self.policies {
+            if p.require_checksum == Some(true) && !has_checksum {
+                return Err(eyre::format_err!(
+                    "The package '{}' does not provide the checksum of resource, which is required by the policy '{}'. Add 'checksum' or 'checksum_url' to the formula",
+                    name,
+                    p.filepath.display()
+                ));
+            }
+
+            if p.require_signature == Some(true) && !has_signature {
+                return Err(eyre::format_err!(
+                    "The package '{}' does not provide the signature of resource, which is required by the policy '{}'. Add 'signature_url' and 'package.public_key' to the formula",
+                    name,
+                    p.filepath.display()
+                ));
+            }
+
+            if p.disallow_hooks == Some(true) && has_hook {
+                return Err(eyre::format_err!(
+                    "The package '{}' defines hooks, which are disallowed by the policy '{}'",
+                    name,
+                    p.filepath.display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use crate::{cask, policy};
+
+    fn new_policies(contents: &[&str]) -> policy::Policies {
+        policy::Policies {
+            policies: contents
+                .iter()
+                .enumerate()
+                .map(|(i, content)| {
+                    let mut p: policy::Policy = toml::from_str(content).unwrap();
+                    p.filepath = PathBuf::from(format!("policy_{}.toml", i));
+                    p
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_is_match() {
+        assert!(policy::is_match("github.com", "github.com"));
+        assert!(policy::is_match("github.com", "GitHub.com"));
+        assert!(policy::is_match("*.example.com", "git.example.com"));
+        assert!(!policy::is_match("*.example.com", "example.com"));
+        assert!(policy::is_match(
+            "github.com/axetroy/*",
+            "github.com/axetroy/dvm"
+        ));
+        assert!(policy::is_match(
+            "github.com/*/dvm",
+            "github.com/axetroy/dvm"
+        ));
+        assert!(policy::is_match("*", "github.com/axetroy/dvm"));
+        assert!(!policy::is_match(
+            "github.com/*/dvm",
+            "github.com/axetroy/gpm"
+        ));
+        assert!(!policy::is_match("github.com", "github.com.evil.com"));
+        assert!(!policy::is_match("a*a", "a"));
+    }
+
+    #[test]
+    fn test_check_source() {
+        let p = new_policies(&[
+            r#"allow_hosts = ["github.com", "*.corp.example.com"]"#,
+            r#"deny_packages = ["github.com/evil/*"]"#,
+        ]);
+
+        p.check_source("github.com/axetroy/dvm").unwrap();
+        p.check_source("git.corp.example.com/team/tool").unwrap();
+        assert!(p.check_source("gitlab.com/axetroy/dvm").is_err());
+        assert!(p.check_source("github.com/evil/tool").is_err());
+
+        let p = new_policies(&[r#"deny_hosts = ["gitlab.com"]
+allow_packages = ["github.com/axetroy/*"]"#]);
+
+        p.check_source("github.com/axetroy/dvm").unwrap();
+        assert!(p.check_source("gitlab.com/axetroy/dvm").is_err());
+        assert!(p.check_source("github.com/other/dvm").is_err());
+        assert!(p.check_package("github.com/other/dvm").is_err());
+
+        // no policy
+        new_policies(&[])
+            .check_source("gitlab.com/any/tool")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_check_install() {
+        let p = new_policies(&["allow_local = false\nrequire_checksum = true"]);
+
+        assert!(p.check_local("./Cask.toml").is_err());
+        assert!(p.check_install("dvm", false, false, false).is_err());
+        p.check_install("dvm", true, false, true).unwrap();
+
+        let p = new_policies(&["require_signature = true\ndisallow_hooks = true"]);
+
+        p.check_local("./Cask.toml").unwrap();
+        assert!(p.check_install("dvm", true, false, false).is_err());
+        assert!(p.check_install("dvm", true, true, true).is_err());
+        p.check_install("dvm", false, true, false).unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let root_dir = env::temp_dir().join("cask_test_policy_load");
+
+        fs::remove_dir_all(&root_dir).ok();
+        fs::create_dir_all(&root_dir).unwrap();
+
+        let c = cask::new(&root_dir);
+        let system_policy_file = root_dir.join("system_policy.toml");
+
+        fs::write(&system_policy_file, "require_checksum = true").unwrap();
+        fs::write(c.policy_file(), "allow_local = false").unwrap();
+        fs::write(c.config_file(), "[security]\nrequire_signature = true").unwrap();
+
+        let p = policy::load_with(&c, &system_policy_file).unwrap();
+
+        assert_eq!(p.policies.len(), 3);
+        assert_eq!(p.policies[0].filepath, system_policy_file);
+        assert!(p.check_local("./Cask.toml").is_err());
+        assert!(p.check_install("dvm", false, true, false).is_err());
+        assert!(p.check_install("dvm", true, false, false).is_err());
+        p.check_install("dvm", true, true, false).unwrap();
+
+        fs::remove_dir_all(&root_dir).ok();
+    }
+}
@@ -0,0 +1,234 @@
+#![deny(warnings)]
+
+use std::{fs, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+use eyre::Report;
+
+// The signature algorithm of minisign
+const MINISIGN_LEGACY: &[u8; 2] = b"Ed"; // sign the file content
+const MINISIGN_PREHASHED: &[u8; 2] = b"ED"; // sign the BLAKE2b-512 of file content
+
+struct PublicKey {
+    key_id: Option<[u8; 8]>, // The key id of minisign, the raw ed25519 key does not have it
+    key: VerifyingKey,
+}
+
+// get the base64 line, the comment lines are skipped
+fn get_payload(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+}
+
+fn decode(content: &str, name: &str) -> Result<Vec<u8>, Report> {
+    let payload = get_payload(content).ok_or_else(|| eyre::format_err!("empty {}", name))?;
+
+    STANDARD
+        .decode(payload)
+        .map_err(|e| eyre::format_err!("invalid {}: {}", name, e))
+}
+
+// parse the minisign public key or the raw ed25519 public key in base64
+fn parse_public_key(content: &str) -> Result<PublicKey, Report> {
+    let bytes = decode(content, "public key")?;
+
+    let (key_id, key) = match bytes.len() {
+        32 => (None, &bytes[..]),
+        42 if &bytes[..2] == MINISIGN_LEGACY => (Some(bytes[2..10].try_into()?), &bytes[10..]),
+        _ => return Err(eyre::format_err!("unsupported public key")),
+    };
+
+    Ok(PublicKey {
+        key_id,
+        key: VerifyingKey::from_bytes(key.try_into()?)?,
+    })
+}
+
+fn verify_minisign(content: &[u8], signature: &str, public_key: &PublicKey) -> Result<(), Report> {
+    let mut lines = signature
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with("untrusted comment:"));
+
+    let invalid = || eyre::format_err!("invalid minisign signature");
+
+    let sig_bytes = STANDARD
+        .decode(lines.next().ok_or_else(invalid)?)
+        .map_err(|_| invalid())?;
+
+    if sig_bytes.len() != 74 {
+        return Err(invalid());
+    }
+
+    let (algorithm, key_id, sig) = (&sig_bytes[..2], &sig_bytes[2..10], &sig_bytes[10..]);
+
+    if let Some(expect_key_id) = &public_key.key_id {
+        if key_id != expect_key_id {
+            return Err(eyre::format_err!(
+                "the signature is not signed by the public key"
+            ));
+        }
+    }
+
+    let signature = Signature::from_slice(sig)?;
+
+    if algorithm == MINISIGN_PREHASHED {
+        let hash = Blake2b512::digest(content);
+
+        public_key.key.verify_strict(&hash, &signature)?;
+    } else if algorithm == MINISIGN_LEGACY {
+        public_key.key.verify_strict(content, &signature)?;
+    } else {
+        return Err(invalid());
+    }
+
+    // the trusted comment is signed with the signature, it's required as minisign does
+    let trusted_comment = lines
+        .next()
+        .and_then(|l| l.strip_prefix("trusted comment: "))
+        .ok_or_else(invalid)?;
+
+    let global_sig = STANDARD
+        .decode(lines.next().ok_or_else(invalid)?)
+        .map_err(|_| invalid())?;
+
+    let mut message = sig.to_vec();
+    message.extend_from_slice(trusted_comment.as_bytes());
+
+    public_key
+        .key
+        .verify_strict(&message, &Signature::from_slice(&global_sig)?)
+        .map_err(|_| eyre::format_err!("the trusted comment of signature is modified"))?;
+
+    Ok(())
+}
+
+// verify the file with the detached signature.
+// the signature can be generated by minisign, or the raw ed25519 signature in base64
+pub fn verify_file(file: &Path, signature: &str, public_key: &str) -> Result<(), Report> {
+    let public_key = parse_public_key(public_key)?;
+
+    let content = fs::read(file)?;
+
+    let is_minisign = signature.trim_start().starts_with("untrusted comment:")
+        || signature.lines().filter(|l| !l.trim().is_empty()).count() > 1;
+
+    if is_minisign {
+        verify_minisign(&content, signature, &public_key)
+    } else {
+        let sig = decode(signature, "signature")?;
+
+        Ok(public_key
+            .key
+            .verify_strict(&content, &Signature::from_slice(&sig)?)?)
+    }
+    .map_err(|e| eyre::format_err!("verify signature of '{}' fail: {}", file.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use blake2::{Blake2b512, Digest};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use crate::signature;
+
+    const KEY_ID: &[u8; 8] = b"\x01\x02\x03\x04\x05\x06\x07\x08";
+
+    fn minisign_public_key(key: &SigningKey) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend_from_slice(KEY_ID);
+        bytes.extend_from_slice(key.verifying_key().as_bytes());
+
+        format!(
+            "untrusted comment: minisign public key\n{}\n",
+            STANDARD.encode(bytes)
+        )
+    }
+
+    fn minisign_signature(key: &SigningKey, content: &[u8], trusted_comment: &str) -> String {
+        let hash = Blake2b512::digest(content);
+        let sig = key.sign(&hash).to_bytes();
+
+        let mut bytes = b"ED".to_vec();
+        bytes.extend_from_slice(KEY_ID);
+        bytes.extend_from_slice(&sig);
+
+        let mut message = sig.to_vec();
+        message.extend_from_slice(trusted_comment.as_bytes());
+
+        format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            STANDARD.encode(bytes),
+            trusted_comment,
+            STANDARD.encode(key.sign(&message).to_bytes())
+        )
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+
+        let file = env::temp_dir().join("cask_test_verify_signature");
+
+        fs::write(&file, "hello").unwrap();
+
+        // minisign
+        let public_key = minisign_public_key(&key);
+        let sig = minisign_signature(&key, b"hello", "timestamp:1700000000");
+
+        signature::verify_file(&file, &sig, &public_key).unwrap();
+
+        assert!(signature::verify_file(
+            &file,
+            &sig.replace("timestamp:1700000000", "timestamp:1800000000"),
+            &public_key
+        )
+        .is_err());
+        // the trusted comment and the global signature are required
+        let without_trusted_comment = sig.lines().take(2).collect::<Vec<_>>().join("\n");
+
+        assert!(signature::verify_file(&file, &without_trusted_comment, &public_key).is_err());
+        assert!(signature::verify_file(
+            &file,
+            &sig.lines().take(3).collect::<Vec<_>>().join("\n"),
+            &public_key
+        )
+        .is_err());
+        assert!(signature::verify_file(
+            &file,
+            &minisign_signature(&other_key, b"hello", "timestamp:1700000000"),
+            &public_key
+        )
+        .is_err());
+        assert!(signature::verify_file(
+            &file,
+            &minisign_signature(&key, b"hello world", "timestamp:1700000000"),
+            &public_key
+        )
+        .is_err());
+
+        // raw ed25519
+        let public_key = STANDARD.encode(key.verifying_key().as_bytes());
+        let sig = STANDARD.encode(key.sign(b"hello").to_bytes());
+
+        signature::verify_file(&file, &sig, &public_key).unwrap();
+
+        assert!(signature::verify_file(
+            &file,
+            &STANDARD.encode(other_key.sign(b"hello").to_bytes()),
+            &public_key
+        )
+        .is_err());
+        assert!(signature::verify_file(&file, &sig, "invalid").is_err());
+
+        fs::remove_file(&file).unwrap();
+    }
+}